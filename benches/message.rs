@@ -0,0 +1,123 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measures creating and dropping errors, compared to a copy of the previous
+//! implementation, which boxed a struct holding a `String`.
+//!
+//! Run with `cargo bench`. Capturing backtraces takes far longer than
+//! storing the message, so `RUST_BACKTRACE` and `RUST_LIB_BACKTRACE` should
+//! not enable them.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error;
+use std::fmt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use string_error::{into_err, new_err, static_err};
+
+const ITERATIONS: u32 = 1_000_000;
+
+const SHORT: &str = "unexpected token";
+const LONG: &str = "unexpected token, expected an identifier or a literal";
+
+/// Runs `f` `ITERATIONS` times and prints the time per iteration.
+fn bench<F>(name: &str, mut f: F)
+    where F: FnMut() -> Box<dyn Error + Send + Sync> {
+    // Warm up the allocator and the backtrace settings.
+    for _ in 0..ITERATIONS / 10 {
+        drop(black_box(f()));
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        drop(black_box(f()));
+    }
+    let elapsed = start.elapsed();
+    println!("{:<40} {:>8.1} ns/iter", name, per_iteration(elapsed));
+}
+
+fn per_iteration(elapsed: Duration) -> f64 {
+    elapsed.as_nanos() as f64 / f64::from(ITERATIONS)
+}
+
+/// The previous implementation, which always copied the message into a
+/// `String`.
+mod baseline {
+    use super::*;
+
+    #[derive(Debug)]
+    struct StaticStrError {
+        error: &'static str
+    }
+
+    impl Error for StaticStrError {}
+
+    impl fmt::Display for StaticStrError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(self.error)
+        }
+    }
+
+    #[derive(Debug)]
+    struct StringError {
+        error: String
+    }
+
+    impl Error for StringError {}
+
+    impl fmt::Display for StringError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.error)
+        }
+    }
+
+    pub fn static_err(e: &'static str) -> Box<dyn Error + Send + Sync> {
+        Box::new(StaticStrError { error: e })
+    }
+
+    pub fn new_err(e: &str) -> Box<dyn Error + Send + Sync> {
+        Box::new(StringError { error: String::from(e) })
+    }
+
+    pub fn into_err(e: String) -> Box<dyn Error + Send + Sync> {
+        Box::new(StringError { error: e })
+    }
+}
+
+fn main() {
+    if Backtrace::capture().status() == BacktraceStatus::Captured {
+        eprintln!("warning: backtraces are enabled and dominate the results");
+    }
+    for (length, message) in [("short", SHORT), ("long", LONG)] {
+        let input = black_box(String::from(message));
+        bench(&format!("baseline new_err ({})", length),
+              || baseline::new_err(&input));
+        bench(&format!("new_err ({})", length), || new_err(&input));
+        // Includes cloning the input, like a caller without a `String`.
+        // The clone has exactly the capacity it needs.
+        bench(&format!("baseline into_err ({})", length),
+              || baseline::into_err(input.clone()));
+        bench(&format!("into_err ({})", length),
+              || into_err(input.clone()));
+        // A formatted string usually has more capacity than it needs.
+        bench(&format!("baseline into_err(format!) ({})", length),
+              || baseline::into_err(format!("{}: {}", input, 42)));
+        bench(&format!("into_err(format!) ({})", length),
+              || into_err(format!("{}: {}", input, 42)));
+        bench(&format!("baseline static_err ({})", length),
+              || baseline::static_err(black_box(message)));
+        bench(&format!("static_err ({})", length),
+              || static_err(black_box(message)));
+    }
+}
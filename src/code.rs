@@ -0,0 +1,251 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Error codes with long explanations and a registry to look them up.
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::error::Error;
+use core::fmt;
+
+use crate::StringError;
+
+/// A stable error code with a message and a long explanation.
+///
+/// Error codes are meant to be declared as `static` items with
+/// `define_errors!`, so that they can be shown to users and looked up in a
+/// `Registry`, similar to rustc's `E0308`. An `ErrorCode` is an error
+/// itself; use `error` or `err` to create a `StringError` that carries the
+/// code along with a location and a backtrace.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::ErrorCode;
+///
+/// static NOT_FOUND: ErrorCode = ErrorCode::new(
+///     "E0001", "configuration file not found",
+///     "The configuration file was not found in any of the searched paths.");
+///
+/// let x = NOT_FOUND.error();
+/// assert_eq!(x.code().unwrap().code(), "E0001");
+/// assert_eq!(x.to_string(), "[E0001] configuration file not found");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorCode {
+    code: &'static str,
+    message: &'static str,
+    explanation: &'static str,
+}
+
+impl ErrorCode {
+    /// Creates an error code.
+    pub const fn new(code: &'static str, message: &'static str,
+                     explanation: &'static str) -> ErrorCode {
+        ErrorCode { code, message, explanation }
+    }
+
+    /// Returns the code, e.g. `"E0001"`.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Returns the error message.
+    pub fn message(&self) -> &'static str {
+        self.message
+    }
+
+    /// Returns the long explanation of the error.
+    pub fn explanation(&self) -> &'static str {
+        self.explanation
+    }
+
+    /// Creates a `StringError` with the message and this code.
+    #[track_caller]
+    pub fn error(&'static self) -> StringError {
+        StringError::new(self.message).with_code(self)
+    }
+
+    /// Creates an error trait object with the message and this code.
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn err(&'static self) -> Box<dyn Error + Send + Sync> {
+        Box::new(self.error())
+    }
+}
+
+impl Error for ErrorCode {
+    fn description(&self) -> &str {
+        self.message
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+/// A set of error codes that can be looked up at runtime.
+///
+/// Usually declared together with the codes by `define_errors!`.
+#[derive(Debug, Clone, Copy)]
+pub struct Registry {
+    codes: &'static [&'static ErrorCode],
+}
+
+impl Registry {
+    /// Creates a registry of the given error codes.
+    pub const fn new(codes: &'static [&'static ErrorCode]) -> Registry {
+        Registry { codes }
+    }
+
+    /// Looks up an error code.
+    pub fn lookup(&self, code: &str) -> Option<&'static ErrorCode> {
+        self.codes.iter().copied().find(|c| c.code == code)
+    }
+
+    /// Looks up the long explanation of an error code.
+    pub fn explain(&self, code: &str) -> Option<&'static str> {
+        self.lookup(code).map(ErrorCode::explanation)
+    }
+
+    /// Returns an iterator over all error codes in the registry.
+    pub fn iter(&self) -> impl Iterator<Item = &'static ErrorCode> {
+        self.codes.iter().copied()
+    }
+}
+
+/// Declares error codes as `static` items, along with a `Registry` that
+/// contains all of them.
+///
+/// Each error is declared as `static NAME = CODE { message: ...,
+/// explanation: ... };`, where `CODE` is an identifier used as the code.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate string_error;
+///
+/// define_errors! {
+///     /// All error codes of the application.
+///     pub static ERRORS;
+///
+///     /// The configuration file is missing.
+///     pub static CONFIG_NOT_FOUND = E0001 {
+///         message: "configuration file not found",
+///         explanation: "The configuration file was not found in any of \
+///                       the searched paths.",
+///     };
+///
+///     pub static PORT_IN_USE = E0002 {
+///         message: "port already in use",
+///         explanation: "Another process is listening on the port.",
+///     };
+/// }
+///
+/// # fn main() {
+/// let x = CONFIG_NOT_FOUND.error();
+/// assert_eq!(x.to_string(), "[E0001] configuration file not found");
+///
+/// let code = x.code().unwrap().code();
+/// assert_eq!(ERRORS.explain(code),
+///            Some("The configuration file was not found in any of the \
+///                  searched paths."));
+/// assert!(ERRORS.lookup("E0002").is_some());
+/// assert!(ERRORS.lookup("E0003").is_none());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! define_errors {
+    (
+        $(#[$registry_attr:meta])*
+        $registry_vis:vis static $registry:ident;
+
+        $(
+            $(#[$attr:meta])*
+            $vis:vis static $name:ident = $code:ident {
+                message: $message:expr,
+                explanation: $explanation:expr $(,)?
+            };
+        )*
+    ) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::ErrorCode = $crate::ErrorCode::new(
+                stringify!($code), $message, $explanation);
+        )*
+
+        $(#[$registry_attr])*
+        $registry_vis static $registry: $crate::Registry =
+            $crate::Registry::new(&[$(&$name),*]);
+    };
+}
+
+#[cfg(all(test, feature = "alloc"))]
+#[allow(deprecated)]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+
+    define_errors! {
+        static REGISTRY;
+
+        static FIRST = E0001 {
+            message: "first error",
+            explanation: "The first error happened.",
+        };
+
+        static SECOND = SECOND_ERROR {
+            message: "second error",
+            explanation: "The second error happened.",
+        };
+    }
+
+    #[test]
+    fn test_define_errors() {
+        assert_eq!(FIRST.code(), "E0001");
+        assert_eq!(FIRST.message(), "first error");
+        assert_eq!(FIRST.explanation(), "The first error happened.");
+        assert_eq!(SECOND.code(), "SECOND_ERROR");
+    }
+
+    #[test]
+    fn test_registry() {
+        assert_eq!(REGISTRY.lookup("E0001"), Some(&FIRST));
+        assert_eq!(REGISTRY.explain("SECOND_ERROR"),
+                   Some("The second error happened."));
+        assert_eq!(REGISTRY.lookup("E0003"), None);
+        let codes: Vec<_> = REGISTRY.iter().map(ErrorCode::code).collect();
+        assert_eq!(codes, ["E0001", "SECOND_ERROR"]);
+    }
+
+    #[test]
+    fn test_error_code_as_error() {
+        assert_eq!(FIRST.to_string(), "[E0001] first error");
+        assert_eq!(FIRST.description(), "first error");
+    }
+
+    #[test]
+    fn test_coded_string_error() {
+        let x = FIRST.err();
+        assert_eq!(crate::code(&*x), Some(&FIRST));
+        assert_eq!(x.to_string(), "[E0001] first error");
+        assert_eq!(x.description(), "first error");
+        assert_eq!(crate::location(&*x).unwrap().line(), line!() - 4);
+        assert_eq!(crate::code(&FIRST), Some(&FIRST));
+        assert_eq!(crate::code(&*crate::static_err("Foo")), None);
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Extension trait to add a message to a `Result` or an `Option`.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::error::Error;
+
+use crate::{into_err, into_err_with_source, static_err, static_err_with_source};
+
+/// Adds a message to the error case of a `Result` or to the `None` case
+/// of an `Option`.
+///
+/// For a `Result`, the original error is kept as the `Error::source` of
+/// the returned error. This includes results with a boxed error, such as
+/// the ones returned by `context`, so that messages can be layered.
+///
+/// # Examples
+///
+/// ```
+/// use std::error::Error;
+/// use std::fs::File;
+/// use string_error::Context;
+///
+/// let x = File::open("/does/not/exist").context("Failed to open file");
+/// let err = x.unwrap_err();
+/// assert_eq!(err.to_string(), "Failed to open file");
+/// assert!(err.source().is_some());
+/// ```
+pub trait Context<T> {
+    /// Wraps the error in an error with the given message.
+    ///
+    /// This does not copy the message, see `static_err`.
+    fn context(self, msg: &'static str)
+               -> Result<T, Box<dyn Error + Send + Sync>>;
+
+    /// Wraps the error in an error with a message built by `f`.
+    ///
+    /// `f` is only called if there is an error to wrap.
+    fn with_context<F>(self, f: F) -> Result<T, Box<dyn Error + Send + Sync>>
+        where F: FnOnce() -> String;
+}
+
+// The methods match instead of using `map_err` and `ok_or_else`, so that
+// `#[track_caller]` records the location of their caller.
+
+// Bounding on `Into<Box<..>>` instead of `Error` also covers boxed errors,
+// such as the ones returned by `context` itself, which do not implement
+// `Error`. Boxed errors are not boxed again.
+impl<T, E> Context<T> for Result<T, E>
+    where E: Into<Box<dyn Error + Send + Sync>> {
+    #[track_caller]
+    fn context(self, msg: &'static str)
+               -> Result<T, Box<dyn Error + Send + Sync>> {
+        match self {
+            Ok(x) => Ok(x),
+            Err(e) => Err(static_err_with_source(msg, e.into())),
+        }
+    }
+
+    #[track_caller]
+    fn with_context<F>(self, f: F) -> Result<T, Box<dyn Error + Send + Sync>>
+        where F: FnOnce() -> String {
+        match self {
+            Ok(x) => Ok(x),
+            Err(e) => Err(into_err_with_source(f(), e.into())),
+        }
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    #[track_caller]
+    fn context(self, msg: &'static str)
+               -> Result<T, Box<dyn Error + Send + Sync>> {
+        match self {
+            Some(x) => Ok(x),
+            None => Err(static_err(msg)),
+        }
+    }
+
+    #[track_caller]
+    fn with_context<F>(self, f: F) -> Result<T, Box<dyn Error + Send + Sync>>
+        where F: FnOnce() -> String {
+        match self {
+            Some(x) => Ok(x),
+            None => Err(into_err(f())),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+#[allow(deprecated)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn io_err() -> Result<(), io::Error> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "not found"))
+    }
+
+    #[test]
+    fn test_result_context() {
+        let x = io_err().context("reading config").unwrap_err();
+        assert_eq!(x.description(), "reading config");
+        assert_eq!(x.source().unwrap().to_string(), "not found");
+    }
+
+    #[test]
+    fn test_result_with_context() {
+        let path = "/etc/x";
+        let x = io_err()
+            .with_context(|| format!("reading {}", path))
+            .unwrap_err();
+        assert_eq!(x.description(), "reading /etc/x");
+        assert_eq!(x.source().unwrap().to_string(), "not found");
+    }
+
+    fn load() -> Result<(), Box<dyn Error + Send + Sync>> {
+        io_err().context("reading config")
+    }
+
+    #[test]
+    fn test_layered_context() {
+        let x = load()
+            .context("loading settings")
+            .with_context(|| format!("starting {}", "server"))
+            .unwrap_err();
+        assert_eq!(x.description(), "starting server");
+        let chain: Vec<_> = crate::report::Sources(Some(&*x))
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(chain, ["starting server", "loading settings",
+                           "reading config", "not found"]);
+    }
+
+    #[test]
+    fn test_boxed_error_is_not_boxed_again() {
+        let inner = crate::static_err("inner");
+        let ptr = &*inner as *const (dyn Error + Send + Sync) as *const u8;
+        let x = Err::<(), _>(inner).context("outer").unwrap_err();
+        let source = x.source().unwrap() as *const dyn Error as *const u8;
+        assert_eq!(source, ptr);
+    }
+
+    #[test]
+    fn test_result_with_context_is_lazy() {
+        let x: Result<i32, io::Error> = Ok(1);
+        let y = x.with_context(|| panic!("must not be called"));
+        assert_eq!(y.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_option_context() {
+        let x: Option<i32> = None;
+        let err = x.context("missing value").unwrap_err();
+        assert_eq!(err.description(), "missing value");
+        assert!(err.source().is_none());
+        assert_eq!(Some(2).context("missing value").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_context_location() {
+        let x = io_err().context("reading config").unwrap_err();
+        let loc = crate::location(&*x).unwrap();
+        assert_eq!((loc.file(), loc.line()), (file!(), line!() - 2));
+
+        let y = None::<i32>.with_context(String::new).unwrap_err();
+        assert_eq!(crate::location(&*y).unwrap().line(), line!() - 1);
+    }
+
+    #[test]
+    fn test_option_with_context() {
+        let x: Option<i32> = None;
+        let err = x.with_context(|| format!("missing {}", "port"))
+            .unwrap_err();
+        assert_eq!(err.description(), "missing port");
+    }
+}
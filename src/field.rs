@@ -0,0 +1,128 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Values of structured key-value fields on string errors.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::fmt;
+
+/// The value of a key-value field attached to a `StringError`.
+///
+/// Values are created with `From`, so `StringError::with_field` accepts
+/// strings, integers, floats and booleans directly.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::{StringError, Value};
+///
+/// let x = StringError::new("Permission denied")
+///     .with_field("user_id", 42)
+///     .with_field("path", "/etc/x");
+/// assert_eq!(x.field("user_id"), Some(&Value::I64(42)));
+/// assert_eq!(x.field("path"), Some(&Value::Str("/etc/x".into())));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Value {
+    /// A string.
+    Str(Cow<'static, str>),
+    /// A signed integer.
+    I64(i64),
+    /// An unsigned integer.
+    U64(u64),
+    /// A floating point number.
+    F64(f64),
+    /// A boolean.
+    Bool(bool),
+}
+
+impl fmt::Display for Value {
+    /// Writes the value; strings are written without quotes.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Str(ref s) => f.write_str(s),
+            Value::I64(n) => write!(f, "{}", n),
+            Value::U64(n) => write!(f, "{}", n),
+            Value::F64(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<&'static str> for Value {
+    fn from(s: &'static str) -> Value {
+        Value::Str(Cow::Borrowed(s))
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::Str(Cow::Owned(s))
+    }
+}
+
+impl From<Cow<'static, str>> for Value {
+    fn from(s: Cow<'static, str>) -> Value {
+        Value::Str(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+macro_rules! impl_from_number {
+    ($variant:ident, $target:ty, $($source:ty),*) => {
+        $(
+            impl From<$source> for Value {
+                fn from(n: $source) -> Value {
+                    Value::$variant(n as $target)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_number!(I64, i64, i8, i16, i32, i64, isize);
+impl_from_number!(U64, u64, u8, u16, u32, u64, usize);
+impl_from_number!(F64, f64, f32, f64);
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+
+    #[test]
+    fn test_from() {
+        assert_eq!(Value::from("x"), Value::Str(Cow::Borrowed("x")));
+        assert_eq!(Value::from(String::from("x")),
+                   Value::Str(Cow::Owned(String::from("x"))));
+        assert_eq!(Value::from(-1i8), Value::I64(-1));
+        assert_eq!(Value::from(7usize), Value::U64(7));
+        assert_eq!(Value::from(0.5f32), Value::F64(0.5));
+        assert_eq!(Value::from(true), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Value::from("/etc/x").to_string(), "/etc/x");
+        assert_eq!(Value::from(42).to_string(), "42");
+        assert_eq!(Value::from(1.5).to_string(), "1.5");
+        assert_eq!(Value::from(false).to_string(), "false");
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Collecting all errors of an iterator of results.
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+use core::error::Error;
+
+use crate::{MultiError, StringError};
+
+/// Extends iterators of results to collect all errors instead of stopping
+/// at the first one.
+///
+/// Each error is wrapped in a `StringError` that names the index of the
+/// failed item and has it as the field `"index"`.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::CollectAll;
+///
+/// let lines = ["1", "x", "3", "300"];
+/// let errors = lines.iter()
+///     .map(|s| s.parse::<u8>())
+///     .collect_all_with(|i| format!("Invalid line {}", i + 1))
+///     .unwrap_err();
+/// assert_eq!(errors.to_string(), "\
+/// 2 errors occurred:
+///     1: Invalid line 2: invalid digit found in string
+///     2: Invalid line 4: number too large to fit in target type");
+///
+/// let numbers = lines[..1].iter().map(|s| s.parse::<u8>()).collect_all();
+/// assert_eq!(numbers.unwrap(), [1]);
+/// ```
+pub trait CollectAll<T, E>: Iterator<Item = Result<T, E>> + Sized {
+    /// Collects all values, or all errors with the message `"item <index>"`.
+    #[track_caller]
+    fn collect_all(self) -> Result<Vec<T>, MultiError> {
+        self.collect_all_with(|i| format!("item {}", i))
+    }
+
+    /// Collects all values, or all errors with the message returned by
+    /// `context` for the index of the failed item.
+    #[track_caller]
+    fn collect_all_with<F, M>(self, context: F) -> Result<Vec<T>, MultiError>
+        where F: FnMut(usize) -> M,
+              M: Into<Cow<'static, str>>;
+}
+
+impl<I, T, E> CollectAll<T, E> for I
+    where I: Iterator<Item = Result<T, E>>,
+          E: Into<Box<dyn Error + Send + Sync>> {
+    #[track_caller]
+    fn collect_all_with<F, M>(self, mut context: F)
+                              -> Result<Vec<T>, MultiError>
+        where F: FnMut(usize) -> M,
+              M: Into<Cow<'static, str>> {
+        let mut values = Vec::new();
+        let mut errors = MultiError::new();
+        for (i, result) in self.enumerate() {
+            match result {
+                Ok(value) => values.push(value),
+                Err(e) => errors.push(
+                    StringError::with_source(context(i), e.into())
+                        .with_field("index", i)),
+            }
+        }
+        errors.into_result().map(|()| values)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+    use crate::{static_err, Value};
+
+    #[test]
+    fn test_collect_all() {
+        let results = vec![Ok(1), Err(static_err("Foo")), Ok(2),
+                           Err(static_err("Bar"))];
+        let errors = results.into_iter().collect_all().unwrap_err();
+        assert_eq!(errors.to_string(), "\
+2 errors occurred:
+    1: item 1: Foo
+    2: item 3: Bar");
+
+        let indices: Vec<_> = errors.iter()
+            .map(|e| e.downcast_ref::<StringError>().unwrap())
+            .map(|e| e.field("index").unwrap().clone())
+            .collect();
+        assert_eq!(indices, [Value::U64(1), Value::U64(3)]);
+        let location = crate::location(&*errors.into_vec()[0]).unwrap();
+        assert_eq!(location.line(), line!() - 12);
+
+        let results: Vec<Result<i32, &str>> = vec![Ok(1), Ok(2)];
+        assert_eq!(results.into_iter().collect_all().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn test_collect_all_with() {
+        let errors = ["a", "1", "b"].iter()
+            .map(|s| s.parse::<i32>())
+            .collect_all_with(|i| format!("line {}", i + 1))
+            .unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, ["line 1", "line 3"]);
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Categories of string errors.
+
+use core::fmt;
+
+/// A category of errors, modelled on `std::io::ErrorKind`.
+///
+/// Every `StringError` has a kind, `ErrorKind::Other` unless set otherwise.
+/// Handlers can match on the kind instead of the error message. Kinds that
+/// are not covered by the built-in variants can be expressed with
+/// `ErrorKind::Custom`.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let throttled = ErrorKind::Custom("throttled");
+/// let x = StringError::new("Too many requests").with_kind(throttled);
+/// assert_eq!(kind(&x), Some(throttled));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An entity was not found.
+    NotFound,
+    /// The operation lacked the necessary privileges to complete.
+    PermissionDenied,
+    /// The connection was refused by the remote server.
+    ConnectionRefused,
+    /// The connection was reset by the remote server.
+    ConnectionReset,
+    /// The connection was aborted by the remote server.
+    ConnectionAborted,
+    /// The operation failed because there is no connection yet.
+    NotConnected,
+    /// An entity already exists.
+    AlreadyExists,
+    /// The operation needs to block to complete, but blocking was not
+    /// requested.
+    WouldBlock,
+    /// A parameter was incorrect.
+    InvalidInput,
+    /// Data not valid for the operation was encountered.
+    InvalidData,
+    /// The operation's timeout expired.
+    TimedOut,
+    /// The operation was interrupted and can typically be retried.
+    Interrupted,
+    /// The operation is not supported.
+    Unsupported,
+    /// The input ended before the operation could complete.
+    UnexpectedEof,
+    /// Memory could not be allocated.
+    OutOfMemory,
+    /// Any error not covered by another kind.
+    #[default]
+    Other,
+    /// A kind defined by the application.
+    Custom(&'static str),
+}
+
+impl ErrorKind {
+    /// Returns a short description of the kind.
+    ///
+    /// For `ErrorKind::Custom`, this is the name of the kind.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ErrorKind::NotFound => "entity not found",
+            ErrorKind::PermissionDenied => "permission denied",
+            ErrorKind::ConnectionRefused => "connection refused",
+            ErrorKind::ConnectionReset => "connection reset",
+            ErrorKind::ConnectionAborted => "connection aborted",
+            ErrorKind::NotConnected => "not connected",
+            ErrorKind::AlreadyExists => "entity already exists",
+            ErrorKind::WouldBlock => "operation would block",
+            ErrorKind::InvalidInput => "invalid input parameter",
+            ErrorKind::InvalidData => "invalid data",
+            ErrorKind::TimedOut => "timed out",
+            ErrorKind::Interrupted => "operation interrupted",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::UnexpectedEof => "unexpected end of file",
+            ErrorKind::OutOfMemory => "out of memory",
+            ErrorKind::Other => "other error",
+            ErrorKind::Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::ErrorKind> for ErrorKind {
+    /// Converts an I/O error kind; kinds without a counterpart become
+    /// `ErrorKind::Other`.
+    fn from(kind: std::io::ErrorKind) -> ErrorKind {
+        use std::io::ErrorKind as Io;
+        match kind {
+            Io::NotFound => ErrorKind::NotFound,
+            Io::PermissionDenied => ErrorKind::PermissionDenied,
+            Io::ConnectionRefused => ErrorKind::ConnectionRefused,
+            Io::ConnectionReset => ErrorKind::ConnectionReset,
+            Io::ConnectionAborted => ErrorKind::ConnectionAborted,
+            Io::NotConnected => ErrorKind::NotConnected,
+            Io::AlreadyExists => ErrorKind::AlreadyExists,
+            Io::WouldBlock => ErrorKind::WouldBlock,
+            Io::InvalidInput => ErrorKind::InvalidInput,
+            Io::InvalidData => ErrorKind::InvalidData,
+            Io::TimedOut => ErrorKind::TimedOut,
+            Io::Interrupted => ErrorKind::Interrupted,
+            Io::Unsupported => ErrorKind::Unsupported,
+            Io::UnexpectedEof => ErrorKind::UnexpectedEof,
+            Io::OutOfMemory => ErrorKind::OutOfMemory,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+
+    #[test]
+    fn test_default() {
+        assert_eq!(ErrorKind::default(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(ErrorKind::NotFound.to_string(), "entity not found");
+        assert_eq!(ErrorKind::Custom("throttled").to_string(), "throttled");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_from_io() {
+        use std::io;
+
+        assert_eq!(ErrorKind::from(io::ErrorKind::TimedOut),
+                   ErrorKind::TimedOut);
+        assert_eq!(ErrorKind::from(io::ErrorKind::AddrInUse),
+                   ErrorKind::Other);
+    }
+}
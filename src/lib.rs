@@ -15,53 +15,763 @@
 //! The `string-error` crate.
 //!
 //! This crate provides a simple way to use a string as an error
-//! trait object, i.e. `Box<std::error::Error>`.
+//! trait object, i.e. `Box<dyn std::error::Error>`.
+//!
+//! All errors created by this crate are `Send` and `Sync`. They are returned
+//! as `Box<dyn Error + Send + Sync>`, which also coerces to
+//! `Box<dyn Error>`.
+//!
+//! # Features
+//!
+//! - `std` (default): Captures backtraces, see `backtrace`. Implies
+//!   `alloc`.
+//! - `alloc`: Enables owned messages, sources, the functions that return
+//!   boxed errors, the `err!` macros and the `Context` trait.
+//! - `serde`: Implements `Serialize` for `StringError` and adds
+//!   `ErrorRecord`, which can be deserialized back into an error. Implies
+//!   `alloc`.
+//!
+//! Without any features, the crate is `no_std` and `StringError::new` can
+//! be used to create errors for string constants. The crate always uses
+//! `core::error::Error`, which is the same trait as `std::error::Error`.
 //!
 //! If you need more sophisticated error handling, you should consider
 //! [error-chain](https://crates.io/crates/error-chain), which also provides
 //! functionality to create simple errors from Strings.
 
-use std::fmt;
-use std::error::Error;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// Tests use `std` even if the crate itself is `no_std`.
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
 
-/// Wraps `&'static str` and implements the `Error` trait for it.
-#[derive(Debug)]
-struct StaticStrError {
-    error: &'static str
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::error::Error;
+use core::cmp::Ordering;
+use core::fmt::{self, Write};
+use core::hash::{Hash, Hasher};
+use core::panic::Location;
+#[cfg(feature = "std")]
+use std::backtrace::{Backtrace, BacktraceStatus};
+
+#[cfg(feature = "alloc")]
+#[macro_use]
+mod macros;
+mod code;
+#[cfg(feature = "alloc")]
+mod context;
+#[cfg(feature = "alloc")]
+mod field;
+#[cfg(feature = "alloc")]
+mod iter;
+mod kind;
+#[cfg(feature = "alloc")]
+mod message;
+#[cfg(feature = "alloc")]
+mod multi;
+mod report;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "alloc")]
+mod shared;
+#[cfg(feature = "alloc")]
+mod thin;
+#[cfg(feature = "alloc")]
+mod validate;
+
+pub use code::{ErrorCode, Registry};
+#[cfg(feature = "alloc")]
+pub use context::Context;
+#[cfg(feature = "alloc")]
+pub use field::Value;
+#[cfg(feature = "alloc")]
+pub use iter::CollectAll;
+pub use kind::ErrorKind;
+#[cfg(feature = "alloc")]
+pub use multi::MultiError;
+pub use report::Report;
+#[cfg(feature = "serde")]
+pub use serialize::ErrorRecord;
+#[cfg(feature = "alloc")]
+pub use shared::{shared_err, SharedError};
+#[cfg(feature = "alloc")]
+pub use thin::ThinError;
+#[cfg(feature = "alloc")]
+pub use validate::{ValidationError, Validator};
+
+#[cfg(feature = "alloc")]
+use message::Message;
+
+/// A string that implements the `Error` trait.
+///
+/// This is the type behind all errors created by this crate. Errors for
+/// string constants (`&'static str`) do not copy the message. Other
+/// messages of up to 30 bytes are stored inline, without a heap allocation;
+/// longer owned strings (`String`) are moved into the error. Use
+/// `downcast_ref` to recognize string errors:
+///
+/// ```
+/// use std::error::Error;
+/// use string_error::StringError;
+///
+/// let x: &(dyn Error + 'static) = &StringError::new("Foo");
+/// let e = x.downcast_ref::<StringError>().unwrap();
+/// assert_eq!(e.message(), "Foo");
+/// ```
+///
+/// The `Debug` output is meant for people: it shows the message, the
+/// location, the fields and the chain of sources, which is what is printed
+/// when `main` returns the error or a test unwraps it. `{:#?}` shows the
+/// structure of the error instead.
+pub struct StringError {
+    #[cfg(feature = "alloc")]
+    message: Message,
+    #[cfg(not(feature = "alloc"))]
+    message: &'static str,
+    prefix: Prefix,
+    kind: ErrorKind,
+    code: Option<&'static ErrorCode>,
+    #[cfg(feature = "alloc")]
+    fields: Vec<(Cow<'static, str>, Value)>,
+    #[cfg(feature = "alloc")]
+    source: Option<Box<dyn Error + Send + Sync>>,
+    location: &'static Location<'static>,
+    #[cfg(feature = "std")]
+    backtrace: Backtrace
 }
 
-impl Error for StaticStrError {
-    fn description(&self) -> &str {
-        self.error
-    }
+/// Controls what `StringError` writes before its message when displayed.
+///
+/// The default is `Prefix::None`: an error displays as its bare message,
+/// no matter which function created it. This keeps reports of chained
+/// errors free of repeated prefixes.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::{Prefix, StringError};
+///
+/// let x = StringError::new("Foo");
+/// assert_eq!(x.to_string(), "Foo");
+///
+/// let y = StringError::new("Foo").with_prefix(Prefix::Error);
+/// assert_eq!(y.to_string(), "Error: Foo");
+///
+/// let z = StringError::new("Foo").with_prefix(Prefix::Custom("warning: "));
+/// assert_eq!(z.to_string(), "warning: Foo");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Prefix {
+    /// Displays the bare message.
+    #[default]
+    None,
+    /// Displays the message prefixed with `"Error: "`.
+    Error,
+    /// Displays the message prefixed with the given string.
+    Custom(&'static str),
 }
 
-impl fmt::Display for StaticStrError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.error)
+impl Prefix {
+    /// Returns the string written before the message.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Prefix::None => "",
+            Prefix::Error => "Error: ",
+            Prefix::Custom(prefix) => prefix,
+        }
     }
 }
 
-/// Wraps an owned `String` and implements the `Error` trait for it.
-#[derive(Debug)]
-struct StringError {
-    error: String
+/// Former name of the string error for string constants.
+///
+/// Errors for `&'static str` and `String` are now both represented by
+/// `StringError`.
+#[deprecated(note = "use `StringError` instead")]
+pub type StaticStrError = StringError;
+
+impl StringError {
+    /// Creates an error for a string constant (`&'static str`) or an owned
+    /// string (`String`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_error::StringError;
+    ///
+    /// let x = StringError::new("Foo");
+    /// let y = StringError::new(format!("Foo {}", 42));
+    /// assert_eq!(x.message(), "Foo");
+    /// assert_eq!(y.message(), "Foo 42");
+    /// ```
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn new<M>(message: M) -> StringError
+        where M: Into<Cow<'static, str>> {
+        StringError::from_message(Message::from(message.into()))
+    }
+
+    /// Creates an error for a reference-counted string (`Arc<str>`).
+    ///
+    /// The error keeps a reference to the string instead of copying it, so
+    /// a message that is already shared, e.g. by a cache, is not copied for
+    /// every error. `into_message` copies it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use string_error::StringError;
+    ///
+    /// let message: Arc<str> = Arc::from("Upstream timed out");
+    /// let x = StringError::from_shared(message.clone());
+    /// assert!(std::ptr::eq(x.message(), &*message));
+    /// ```
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn from_shared(message: Arc<str>) -> StringError {
+        StringError::from_message(Message::Shared(message))
+    }
+
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub(crate) fn from_message(message: Message) -> StringError {
+        StringError {
+            message,
+            prefix: Prefix::default(),
+            kind: ErrorKind::default(),
+            code: None,
+            fields: Vec::new(),
+            source: None,
+            location: Location::caller(),
+            #[cfg(feature = "std")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Creates an error for a string constant (`&'static str`).
+    ///
+    /// Without the `alloc` feature, errors can only be created for string
+    /// constants.
+    #[cfg(not(feature = "alloc"))]
+    #[track_caller]
+    pub fn new(message: &'static str) -> StringError {
+        StringError {
+            message,
+            prefix: Prefix::default(),
+            kind: ErrorKind::default(),
+            code: None,
+            location: Location::caller(),
+        }
+    }
+
+    /// Creates an error for a string constant in a constant expression.
+    ///
+    /// This allows declaring errors as `static` items, which can be returned
+    /// as `&'static (dyn Error + Send + Sync)` without any allocation. The
+    /// location of such errors is where they are declared, and they never
+    /// capture a backtrace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::error::Error;
+    /// use string_error::{ErrorKind, StringError};
+    ///
+    /// static NOT_FOUND: StringError = StringError::from_static("Not found")
+    ///     .with_kind(ErrorKind::NotFound);
+    ///
+    /// fn find(key: &str) -> Result<u32, &'static (dyn Error + Send + Sync)> {
+    ///     match key {
+    ///         "x" => Ok(42),
+    ///         _ => Err(&NOT_FOUND),
+    ///     }
+    /// }
+    ///
+    /// let x = find("y").unwrap_err();
+    /// assert_eq!(x.to_string(), "Not found");
+    /// assert_eq!(string_error::kind(x), Some(ErrorKind::NotFound));
+    /// ```
+    #[track_caller]
+    pub const fn from_static(message: &'static str) -> StringError {
+        StringError {
+            #[cfg(feature = "alloc")]
+            message: Message::Static(message),
+            #[cfg(not(feature = "alloc"))]
+            message,
+            prefix: Prefix::None,
+            kind: ErrorKind::Other,
+            code: None,
+            #[cfg(feature = "alloc")]
+            fields: Vec::new(),
+            #[cfg(feature = "alloc")]
+            source: None,
+            location: Location::caller(),
+            #[cfg(feature = "std")]
+            backtrace: Backtrace::disabled(),
+        }
+    }
+
+    /// Creates an error for a string constant or an owned string that was
+    /// caused by `source`.
+    #[cfg(feature = "alloc")]
+    #[track_caller]
+    pub fn with_source<M>(message: M, source: Box<dyn Error + Send + Sync>)
+                          -> StringError
+        where M: Into<Cow<'static, str>> {
+        let mut e = StringError::new(message);
+        e.source = Some(source);
+        e
+    }
+
+    /// Sets what is displayed before the message, see `Prefix`.
+    pub const fn with_prefix(mut self, prefix: Prefix) -> StringError {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Returns what is displayed before the message.
+    pub fn prefix(&self) -> Prefix {
+        self.prefix
+    }
+
+    /// Sets the kind of the error, see `ErrorKind`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_error::{ErrorKind, StringError};
+    ///
+    /// let x = StringError::new("Foo").with_kind(ErrorKind::TimedOut);
+    /// assert_eq!(x.kind(), ErrorKind::TimedOut);
+    /// ```
+    pub const fn with_kind(mut self, kind: ErrorKind) -> StringError {
+        self.kind = kind;
+        self
+    }
+
+    /// Returns the kind of the error.
+    ///
+    /// This is `ErrorKind::Other` unless set otherwise.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Sets the error code, see `ErrorCode`.
+    ///
+    /// The code is displayed before the message. Usually, coded errors are
+    /// created with `ErrorCode::error`, which also uses the message of the
+    /// code.
+    pub const fn with_code(mut self, code: &'static ErrorCode) -> StringError {
+        self.code = Some(code);
+        self
+    }
+
+    /// Returns the error code, if any.
+    pub fn code(&self) -> Option<&'static ErrorCode> {
+        self.code
+    }
+
+    /// Attaches a key-value field to the error.
+    ///
+    /// Fields are not part of the message: they are only displayed in
+    /// alternate mode (`{:#}`), one per line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_error::StringError;
+    ///
+    /// let x = StringError::new("Permission denied")
+    ///     .with_field("user_id", 42)
+    ///     .with_field("path", "/etc/x");
+    /// assert_eq!(x.to_string(), "Permission denied");
+    /// let verbose = format!("{:#}", x);
+    /// assert!(verbose.contains("\n    user_id = 42\n    path = /etc/x"));
+    ///
+    /// let keys: Vec<_> = x.fields().map(|(key, _)| key).collect();
+    /// assert_eq!(keys, ["user_id", "path"]);
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn with_field<K, V>(mut self, key: K, value: V) -> StringError
+        where K: Into<Cow<'static, str>>, V: Into<Value> {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+
+    /// Returns the value of the first field with the given key.
+    #[cfg(feature = "alloc")]
+    pub fn field(&self, key: &str) -> Option<&Value> {
+        self.fields.iter().find(|f| f.0 == key).map(|f| &f.1)
+    }
+
+    /// Returns an iterator over the key-value fields, in the order they were
+    /// attached.
+    #[cfg(feature = "alloc")]
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.fields.iter().map(|f| (&*f.0, &f.1))
+    }
+
+    /// Returns the error message.
+    ///
+    /// This is the message without the prefix.
+    #[cfg(feature = "alloc")]
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+
+    /// Returns the error message.
+    ///
+    /// This is the message without the prefix.
+    #[cfg(not(feature = "alloc"))]
+    pub fn message(&self) -> &str {
+        self.message
+    }
+
+    /// Returns the error message if it is a string constant.
+    ///
+    /// Returns `None` for all other messages, which are owned by the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_error::StringError;
+    ///
+    /// let x = StringError::new("Foo");
+    /// assert_eq!(x.static_message(), Some("Foo"));
+    /// # #[cfg(feature = "alloc")] {
+    /// let y = StringError::new(format!("Foo {}", 42));
+    /// assert_eq!(y.static_message(), None);
+    /// # }
+    /// ```
+    pub fn static_message(&self) -> Option<&'static str> {
+        #[cfg(feature = "alloc")]
+        {
+            match self.message {
+                Message::Static(s) => Some(s),
+                _ => None,
+            }
+        }
+        #[cfg(not(feature = "alloc"))]
+        {
+            Some(self.message)
+        }
+    }
+
+    /// Consumes the error and returns the error message.
+    ///
+    /// This returns `Cow::Borrowed` for string constants and `Cow::Owned`
+    /// for all other messages.
+    #[cfg(feature = "alloc")]
+    pub fn into_message(self) -> Cow<'static, str> {
+        self.message.into_cow()
+    }
+
+    /// Returns the source location at which the error was created.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns the backtrace captured when the error was created.
+    ///
+    /// See `string_error::backtrace` for when backtraces are captured.
+    #[cfg(feature = "std")]
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+
+    /// Returns what string errors are compared and hashed by.
+    fn key(&self) -> (&str, ErrorKind, Option<&'static ErrorCode>) {
+        (self.message(), self.kind, self.code)
+    }
+
+    /// Appends the location, the fields and a captured backtrace in
+    /// alternate (`{:#}`) display mode.
+    fn fmt_details(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !f.alternate() {
+            return Ok(());
+        }
+        write!(f, " (at {})", self.location)?;
+        #[cfg(feature = "alloc")]
+        for (key, value) in self.fields() {
+            write!(f, "\n    {} = {}", key, value)?;
+        }
+        #[cfg(feature = "std")]
+        {
+            if self.backtrace.status() == BacktraceStatus::Captured {
+                write!(f, "\n\nStack backtrace:\n{}", self.backtrace)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Error for StringError {
     fn description(&self) -> &str {
-        &self.error
+        self.message()
+    }
+
+    #[cfg(feature = "alloc")]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|e| &**e as &(dyn Error + 'static))
+    }
+}
+
+impl fmt::Debug for StringError {
+    /// Writes the message, the location, the fields and the chain of
+    /// sources, e.g.:
+    ///
+    /// ```text
+    /// Failed to load config (at src/main.rs:10:5)
+    ///     path = /etc/x
+    ///
+    /// Caused by:
+    ///     0: No such file or directory (os error 2)
+    /// ```
+    ///
+    /// In alternate mode (`{:#?}`), writes the fields of the struct.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            let mut d = f.debug_struct("StringError");
+            d.field("message", &self.message())
+                .field("prefix", &self.prefix)
+                .field("kind", &self.kind)
+                .field("code", &self.code);
+            #[cfg(feature = "alloc")]
+            d.field("fields", &self.fields).field("source", &self.source);
+            d.field("location", &self.location);
+            #[cfg(feature = "std")]
+            d.field("backtrace", &self.backtrace);
+            return d.finish();
+        }
+        write!(report::Indented::new(f, "Error: ".len()), "{}", self)?;
+        write!(f, " (at {})", self.location)?;
+        #[cfg(feature = "alloc")]
+        for (key, value) in self.fields() {
+            write!(f, "\n    {} = {}", key, value)?;
+        }
+        report::fmt_causes(f, self)
     }
 }
 
 impl fmt::Display for StringError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error: {}", self.error)
+        f.write_str(self.prefix.as_str())?;
+        if let Some(code) = self.code {
+            write!(f, "[{}] ", code.code())?;
+        }
+        f.write_str(self.message())?;
+        self.fmt_details(f)
+    }
+}
+
+/// String errors are compared by message, kind and code, in that order.
+///
+/// The prefix, the fields, the source, the location and the backtrace are
+/// ignored, so errors created at different places compare equal.
+impl PartialEq for StringError {
+    fn eq(&self, other: &StringError) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for StringError {}
+
+impl PartialOrd for StringError {
+    fn partial_cmp(&self, other: &StringError) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
+impl Ord for StringError {
+    fn cmp(&self, other: &StringError) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for StringError {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+/// Compares the message of a string error with a string.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::StringError;
+///
+/// let x = Box::new(StringError::new("Foo"));
+/// assert_eq!(*x, "Foo");
+/// assert!("Bar" != *x);
+/// ```
+impl PartialEq<str> for StringError {
+    fn eq(&self, other: &str) -> bool {
+        self.message() == other
+    }
+}
+
+impl PartialEq<&str> for StringError {
+    fn eq(&self, other: &&str) -> bool {
+        self.message() == *other
+    }
+}
+
+impl PartialEq<StringError> for str {
+    fn eq(&self, other: &StringError) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<StringError> for &str {
+    fn eq(&self, other: &StringError) -> bool {
+        other == self
+    }
+}
+
+/// Returns the source location at which a string error was created.
+///
+/// All functions and macros of this crate that create errors record the
+/// location of their caller. Returns `None` if `e` was not created by this
+/// crate.
+///
+/// The location is also shown when the error is displayed in alternate
+/// mode (`{:#}`).
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = StringError::new("Foo");
+/// assert_eq!(location(&x).unwrap().line(), line!() - 1);
+/// assert!(location(&std::fmt::Error).is_none());
+/// ```
+pub fn location(e: &(dyn Error + 'static))
+                -> Option<&'static Location<'static>> {
+    unshare(e).downcast_ref::<StringError>().map(StringError::location)
+}
+
+/// Returns the kind of an error.
+///
+/// For a `StringError`, this is its `kind`. With the `std` feature, the
+/// kind of a `std::io::Error` is converted as well. Returns `None` for
+/// other errors.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = StringError::new("Foo").with_kind(ErrorKind::InvalidInput);
+/// assert_eq!(kind(&x), Some(ErrorKind::InvalidInput));
+/// assert_eq!(kind(&std::fmt::Error), None);
+///
+/// // Requires the `std` feature.
+/// # #[cfg(feature = "std")] {
+/// use std::io;
+///
+/// let io = io::Error::new(io::ErrorKind::NotFound, "Bar");
+/// assert_eq!(kind(&io), Some(ErrorKind::NotFound));
+/// # }
+/// ```
+pub fn kind(e: &(dyn Error + 'static)) -> Option<ErrorKind> {
+    let e = unshare(e);
+    if let Some(e) = e.downcast_ref::<StringError>() {
+        return Some(e.kind());
+    }
+    #[cfg(feature = "std")]
+    {
+        if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            return Some(e.kind().into());
+        }
+    }
+    None
+}
+
+/// Returns the error code of an error.
+///
+/// Returns the code of a `StringError`, or the `ErrorCode` itself if `e` is
+/// one. Returns `None` for other errors.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// static TIMEOUT: ErrorCode = ErrorCode::new(
+///     "E0042", "request timed out", "The server did not respond in time.");
+///
+/// let x = TIMEOUT.error();
+/// assert_eq!(code(&x).map(ErrorCode::code), Some("E0042"));
+/// assert_eq!(code(&StringError::new("Foo")), None);
+/// ```
+pub fn code<'a>(e: &'a (dyn Error + 'static)) -> Option<&'a ErrorCode> {
+    let e = unshare(e);
+    if let Some(e) = e.downcast_ref::<StringError>() {
+        e.code()
+    } else {
+        e.downcast_ref::<ErrorCode>()
+    }
+}
+
+/// Returns the backtrace captured when a string error was created.
+///
+/// Backtraces are only captured if enabled through the `RUST_BACKTRACE` or
+/// `RUST_LIB_BACKTRACE` environment variables, see
+/// `std::backtrace::Backtrace::capture`; otherwise the returned backtrace
+/// is disabled. Returns `None` if `e` was not created by this crate.
+///
+/// The backtrace is also shown when the error is displayed in alternate
+/// mode (`{:#}`).
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = static_err("Foo");
+/// assert!(backtrace(&*x).is_some());
+/// assert!(backtrace(&std::fmt::Error).is_none());
+/// ```
+#[cfg(feature = "std")]
+pub fn backtrace<'a>(e: &'a (dyn Error + 'static)) -> Option<&'a Backtrace> {
+    unshare(e).downcast_ref::<StringError>().map(StringError::backtrace)
+}
+
+/// Returns the error wrapped by a `SharedError`, or `e` itself.
+///
+/// The functions above inspect the wrapped error, so that sharing an error
+/// does not lose its kind, code, location or backtrace.
+pub(crate) fn unshare<'a>(e: &'a (dyn Error + 'static))
+                          -> &'a (dyn Error + 'static) {
+    #[cfg(feature = "alloc")]
+    {
+        if let Some(shared) = e.downcast_ref::<SharedError>() {
+            return unshare(shared.get_ref());
+        }
+    }
+    e
+}
+
 /// Creates an error trait object for a string constant (`&'static str`).
 ///
+/// This allocates the error. To report a constant error without any
+/// allocation, declare it as a `static` with `StringError::from_static`.
+///
 /// # Examples
 ///
 /// ```
@@ -70,14 +780,58 @@ impl fmt::Display for StringError {
 /// let x = static_err("Foo");
 /// assert_eq!(x.description(), "Foo");
 /// ```
-pub fn static_err(e: &'static str) -> Box<Error> {
-    Box::new(StaticStrError { error: e })
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn static_err(e: &'static str) -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::new(e))
+}
+
+/// Creates an error trait object for a string constant (`&'static str`)
+/// that was caused by another error.
+///
+/// The `source` is returned by `Error::source`, so the cause chain of the
+/// returned error stays intact.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = static_err_with_source("Foo", static_err("Bar"));
+/// assert_eq!(x.to_string(), "Foo");
+/// assert_eq!(x.source().unwrap().to_string(), "Bar");
+/// ```
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn static_err_with_source(
+    e: &'static str,
+    source: Box<dyn Error + Send + Sync>,
+) -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::with_source(e, source))
+}
+
+/// Creates an error trait object for a string constant (`&'static str`)
+/// with the given kind.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = static_err_with_kind("Foo", ErrorKind::NotFound);
+/// assert_eq!(kind(&*x), Some(ErrorKind::NotFound));
+/// ```
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn static_err_with_kind(e: &'static str, kind: ErrorKind)
+                            -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::new(e).with_kind(kind))
 }
 
 /// Creates an error trait object for a string (`&str`).
 ///
-/// This copies the argument into an owned string. To avoid the copy, use
-/// either `into_err` or `static_err`.
+/// This copies the argument; only strings longer than 30 bytes are copied
+/// to the heap. To avoid the copy, use either `into_err` or `static_err`.
 /// 
 /// # Examples
 ///
@@ -87,8 +841,59 @@ pub fn static_err(e: &'static str) -> Box<Error> {
 /// let x = new_err("Foo");
 /// assert_eq!(x.description(), "Foo");
 /// ```
-pub fn new_err(e: &str) -> Box<Error> {
-    Box::new(StringError { error: String::from(e) })
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn new_err(e: &str) -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::from_message(Message::copy(e)))
+}
+
+/// Creates an error trait object for a string (`&str`) that was caused by
+/// another error.
+///
+/// This copies the argument; only strings longer than 30 bytes are copied
+/// to the heap. To avoid the copy, use either `into_err_with_source` or
+/// `static_err_with_source`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use string_error::*;
+///
+/// let cause = io::Error::new(io::ErrorKind::NotFound, "no such file");
+/// let x = new_err_with_source("Failed to load config", Box::new(cause));
+/// assert_eq!(x.source().unwrap().to_string(), "no such file");
+/// ```
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn new_err_with_source(
+    e: &str,
+    source: Box<dyn Error + Send + Sync>,
+) -> Box<dyn Error + Send + Sync> {
+    let mut x = StringError::from_message(Message::copy(e));
+    x.source = Some(source);
+    Box::new(x)
+}
+
+/// Creates an error trait object for a string (`&str`) with the given kind.
+///
+/// This copies the argument; only strings longer than 30 bytes are copied
+/// to the heap. To avoid the copy, use either `into_err_with_kind` or
+/// `static_err_with_kind`.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = new_err_with_kind("Foo", ErrorKind::TimedOut);
+/// assert_eq!(kind(&*x), Some(ErrorKind::TimedOut));
+/// ```
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn new_err_with_kind(e: &str, kind: ErrorKind)
+                         -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::from_message(Message::copy(e)).with_kind(kind))
 }
 
 /// Creates an error trait object for an owned string (`String`).
@@ -103,14 +908,80 @@ pub fn new_err(e: &str) -> Box<Error> {
 /// let x = into_err(String::from("Foo"));
 /// assert_eq!(x.description(), "Foo");
 /// ```
-pub fn into_err(e: String) -> Box<Error> {
-    Box::new(StringError { error: e })
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn into_err(e: String) -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::new(e))
+}
+
+/// Creates an error trait object for an owned string (`String`) that was
+/// caused by another error.
+///
+/// This takes ownership of the `String` argument.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = into_err_with_source(String::from("Foo"), new_err("Bar"));
+/// assert_eq!(x.source().unwrap().to_string(), "Bar");
+/// ```
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn into_err_with_source(
+    e: String,
+    source: Box<dyn Error + Send + Sync>,
+) -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::with_source(e, source))
 }
 
-#[cfg(test)]
+/// Creates an error trait object for an owned string (`String`) with the
+/// given kind.
+///
+/// This takes ownership of the `String` argument.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = into_err_with_kind(String::from("Foo"), ErrorKind::Other);
+/// assert_eq!(kind(&*x), Some(ErrorKind::Other));
+/// ```
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn into_err_with_kind(e: String, kind: ErrorKind)
+                          -> Box<dyn Error + Send + Sync> {
+    Box::new(StringError::new(e).with_kind(kind))
+}
+
+/// Creates an error trait object from format arguments.
+///
+/// Used by the `err!` family of macros; do not call it directly.
+#[doc(hidden)]
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn __format_err(args: fmt::Arguments)
+                    -> Box<dyn Error + Send + Sync> {
+    Box::new(__format_error(args))
+}
+
+/// Creates a `StringError` from format arguments.
+///
+/// Used by the `err!` family of macros; do not call it directly.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[track_caller]
+pub fn __format_error(args: fmt::Arguments) -> StringError {
+    StringError::from_message(Message::format(args))
+}
+
+#[cfg(all(test, feature = "std"))]
+#[allow(deprecated)]
 mod tests {
     use super::*;
-    static SOME_STRING : &'static str = "This is a String?!";
+    static SOME_STRING : &str = "This is a String?!";
 
     #[test]
     fn test_static_err() {
@@ -132,4 +1003,421 @@ mod tests {
         assert_eq!(x.description(), SOME_STRING);
         assert!(x.cause().is_none());
     }
+
+    #[test]
+    fn test_static_err_with_source() {
+        let x = static_err_with_source(SOME_STRING, static_err("cause"));
+        assert_eq!(x.description(), SOME_STRING);
+        assert_eq!(x.source().unwrap().description(), "cause");
+    }
+
+    #[test]
+    fn test_new_err_with_source() {
+        let x = new_err_with_source(SOME_STRING, new_err("cause"));
+        assert_eq!(x.description(), SOME_STRING);
+        assert_eq!(x.source().unwrap().description(), "cause");
+    }
+
+    #[test]
+    fn test_into_err_with_source() {
+        let x = into_err_with_source(String::from(SOME_STRING),
+                                     into_err(String::from("cause")));
+        assert_eq!(x.description(), SOME_STRING);
+        assert_eq!(x.source().unwrap().description(), "cause");
+    }
+
+    #[test]
+    fn test_source_chain() {
+        let io = std::io::Error::other("io");
+        let x = static_err_with_source(
+            "outer", new_err_with_source("middle", Box::new(io)));
+        let mut chain = Vec::new();
+        let mut current: Option<&dyn Error> = Some(&*x);
+        while let Some(e) = current {
+            chain.push(e.to_string());
+            current = e.source();
+        }
+        assert_eq!(chain, ["outer", "middle", "io"]);
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let x = new_err(SOME_STRING);
+        let bt = backtrace(&*x).unwrap();
+        if bt.status() == BacktraceStatus::Captured {
+            assert!(format!("{:#}", x).contains("Stack backtrace:"));
+        } else {
+            assert!(!format!("{:#}", x).contains("Stack backtrace:"));
+        }
+        assert!(backtrace(&std::io::Error::other("io")).is_none());
+    }
+
+    #[test]
+    fn test_location() {
+        let line = line!() + 1;
+        let x = static_err(SOME_STRING);
+        let loc = location(&*x).unwrap();
+        assert_eq!(loc.file(), file!());
+        assert_eq!(loc.line(), line);
+        assert!(format!("{:#}", x)
+                .starts_with(&format!("{} (at {})", SOME_STRING, loc)));
+
+        let y = err!("{}", SOME_STRING.len());
+        assert_eq!(location(&*y).unwrap().line(), line!() - 1);
+        assert!(location(&std::io::Error::other("io")).is_none());
+    }
+
+    #[test]
+    fn test_downcast() {
+        let x = static_err_with_source(SOME_STRING, new_err("cause"));
+        let e = x.downcast_ref::<StringError>().unwrap();
+        assert_eq!(e.message(), SOME_STRING);
+        assert_eq!(e.source().unwrap().downcast_ref::<StringError>()
+                   .unwrap().message(), "cause");
+
+        let y = into_err(String::from(SOME_STRING));
+        let e = y.downcast::<StringError>().unwrap();
+        assert_eq!(e.into_message(), SOME_STRING);
+
+        let io: Box<dyn Error + Send + Sync> =
+            Box::new(std::io::Error::other("io"));
+        assert!(io.downcast_ref::<StringError>().is_none());
+    }
+
+    #[test]
+    fn test_concrete_type() {
+        let x = StringError::new(SOME_STRING);
+        assert_eq!(x.location().line(), line!() - 1);
+        assert_eq!(x.message(), SOME_STRING);
+
+        let y = StringError::with_source(String::from(SOME_STRING),
+                                         Box::new(x));
+        assert_eq!(y.message(), SOME_STRING);
+        assert!(y.source().is_some());
+    }
+
+    #[test]
+    fn test_message_storage() {
+        let x = static_err(SOME_STRING).downcast::<StringError>().unwrap();
+        assert!(std::ptr::eq(x.static_message().unwrap(), SOME_STRING));
+        match x.into_message() {
+            Cow::Borrowed(m) => assert!(std::ptr::eq(m, SOME_STRING)),
+            Cow::Owned(_) => panic!("static_err must not copy"),
+        }
+        let y = new_err(SOME_STRING).downcast::<StringError>().unwrap();
+        assert!(matches!(y.message, Message::Inline(_)));
+        assert_eq!(y.static_message(), None);
+        assert!(matches!(y.into_message(), Cow::Owned(_)));
+        let z = into_err(String::from(SOME_STRING))
+            .downcast::<StringError>().unwrap();
+        assert!(matches!(z.into_message(), Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_display_is_consistent() {
+        assert_eq!(static_err(SOME_STRING).to_string(), SOME_STRING);
+        assert_eq!(new_err(SOME_STRING).to_string(), SOME_STRING);
+        assert_eq!(into_err(String::from(SOME_STRING)).to_string(),
+                   SOME_STRING);
+        assert_eq!(err!("{}", SOME_STRING).to_string(), SOME_STRING);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let owned = || StringError::new(String::from(SOME_STRING));
+        let constant = || StringError::new(SOME_STRING);
+        for new in [&owned as &dyn Fn() -> StringError, &constant] {
+            let x = new();
+            assert_eq!(x.prefix(), Prefix::None);
+            assert_eq!(x.to_string(), SOME_STRING);
+
+            let y = new().with_prefix(Prefix::Error);
+            assert_eq!(y.to_string(), format!("Error: {}", SOME_STRING));
+            assert_eq!(y.message(), SOME_STRING);
+
+            let z = new().with_prefix(Prefix::Custom("oops: "));
+            assert_eq!(z.to_string(), format!("oops: {}", SOME_STRING));
+            assert_eq!(z.description(), SOME_STRING);
+        }
+    }
+
+    #[test]
+    fn test_prefix_in_chain() {
+        let inner = StringError::new("inner").with_prefix(Prefix::Error);
+        let outer = StringError::with_source("outer", Box::new(inner))
+            .with_prefix(Prefix::Error);
+        assert_eq!(outer.to_string(), "Error: outer");
+        assert_eq!(outer.source().unwrap().to_string(), "Error: inner");
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(kind(&*static_err(SOME_STRING)), Some(ErrorKind::Other));
+        let x = static_err_with_kind(SOME_STRING, ErrorKind::NotFound);
+        assert_eq!(kind(&*x), Some(ErrorKind::NotFound));
+        assert_eq!(x.description(), SOME_STRING);
+        let y = new_err_with_kind(SOME_STRING, ErrorKind::Custom("retry"));
+        assert_eq!(kind(&*y), Some(ErrorKind::Custom("retry")));
+        let z = into_err_with_kind(String::from(SOME_STRING),
+                                   ErrorKind::TimedOut);
+        assert_eq!(z.downcast_ref::<StringError>().unwrap().kind(),
+                   ErrorKind::TimedOut);
+
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "io");
+        assert_eq!(kind(&io), Some(ErrorKind::TimedOut));
+        assert_eq!(kind(&std::fmt::Error), None);
+    }
+
+    #[test]
+    fn test_fields() {
+        let x = StringError::new(SOME_STRING)
+            .with_field("user_id", 42)
+            .with_field(String::from("path"), "/etc/x")
+            .with_field("retry", false);
+        let fields: Vec<_> = x.fields()
+            .map(|(k, v)| (k.to_owned(), v.clone()))
+            .collect();
+        assert_eq!(fields, [
+            (String::from("user_id"), Value::I64(42)),
+            (String::from("path"), Value::from("/etc/x")),
+            (String::from("retry"), Value::Bool(false)),
+        ]);
+        assert_eq!(x.field("retry"), Some(&Value::Bool(false)));
+        assert_eq!(x.field("missing"), None);
+
+        assert_eq!(x.to_string(), SOME_STRING);
+        let verbose = format!("{:#}", x);
+        assert!(verbose.starts_with(&format!(
+            "{} (at {})\n    user_id = 42\n    path = /etc/x\n    \
+             retry = false", SOME_STRING, x.location())));
+    }
+
+    #[test]
+    fn test_debug() {
+        let x = StringError::with_source(
+            "Failed to load config",
+            static_err_with_source("Failed to read /etc/x",
+                                   static_err("no such file")))
+            .with_field("path", "/etc/x");
+        assert_eq!(format!("{:?}", x), format!("\
+Failed to load config (at {})
+    path = /etc/x
+
+Caused by:
+    0: Failed to read /etc/x
+    1: no such file", x.location()));
+
+        let y = StringError::new("first\nsecond").with_prefix(Prefix::Error);
+        assert_eq!(format!("{:?}", y),
+                   format!("Error: first\n       second (at {})",
+                           y.location()));
+
+        let verbose = format!("{:#?}", x);
+        assert!(verbose.starts_with("StringError {\n    message: \
+                                     \"Failed to load config\","));
+        assert!(verbose.contains("kind: Other,"));
+        assert!(verbose.contains("source: Some("));
+    }
+
+    static STATIC_ERROR: StringError = StringError::from_static(SOME_STRING)
+        .with_prefix(Prefix::Error)
+        .with_kind(ErrorKind::InvalidData);
+    const STATIC_LINE: u32 = line!() - 3;
+
+    fn static_error() -> Result<(), &'static (dyn Error + Send + Sync)> {
+        Err(&STATIC_ERROR)
+    }
+
+    #[test]
+    fn test_from_static() {
+        let x = static_error().unwrap_err();
+        assert_eq!(x.to_string(), format!("Error: {}", SOME_STRING));
+        assert_eq!(kind(x), Some(ErrorKind::InvalidData));
+        assert_eq!(location(x).unwrap().line(), STATIC_LINE);
+        assert_eq!(backtrace(x).unwrap().status(),
+                   BacktraceStatus::Disabled);
+        let e = x.downcast_ref::<StringError>().unwrap();
+        assert!(std::ptr::eq(e, &STATIC_ERROR));
+        assert!(matches!(e.message, Message::Static(_)));
+    }
+
+    #[test]
+    // The backtrace is resolved lazily, but not part of the hash.
+    #[allow(clippy::mutable_key_type)]
+    fn test_eq_and_hash() {
+        use std::collections::HashMap;
+
+        static CODE: ErrorCode = ErrorCode::new("E0001", "Foo", "");
+
+        let x = StringError::new("Foo");
+        let y = StringError::new(String::from("Foo"))
+            .with_prefix(Prefix::Error)
+            .with_field("user_id", 42);
+        assert_eq!(x, y);
+        assert_ne!(x, StringError::new("Bar"));
+        assert_ne!(x, StringError::new("Foo").with_kind(ErrorKind::NotFound));
+        assert_ne!(x, StringError::new("Foo").with_code(&CODE));
+
+        let mut counts = HashMap::new();
+        for e in [x, y, StringError::new("Bar")] {
+            *counts.entry(e).or_insert(0) += 1;
+        }
+        assert_eq!(counts[&StringError::new("Foo")], 2);
+        assert_eq!(counts[&StringError::new("Bar")], 1);
+    }
+
+    #[test]
+    fn test_ord() {
+        let mut errors = [
+            StringError::new("b"),
+            StringError::new("a").with_kind(ErrorKind::Other),
+            StringError::new("a").with_kind(ErrorKind::NotFound),
+        ];
+        errors.sort();
+        let keys: Vec<_> = errors.iter()
+            .map(|e| (e.message(), e.kind()))
+            .collect();
+        assert_eq!(keys, [("a", ErrorKind::NotFound), ("a", ErrorKind::Other),
+                          ("b", ErrorKind::Other)]);
+    }
+
+    #[test]
+    fn test_eq_str() {
+        let x = static_err(SOME_STRING).downcast::<StringError>().unwrap();
+        assert_eq!(*x, SOME_STRING);
+        assert_eq!(SOME_STRING, *x);
+        assert!(*x == *SOME_STRING);
+        assert!(*SOME_STRING == *x);
+        let y = StringError::new("Foo").with_prefix(Prefix::Error);
+        assert_eq!(y, "Foo");
+        assert_ne!(y, "Error: Foo");
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        let x = new_err_with_source(SOME_STRING, static_err("cause"));
+        assert_send_sync(&x);
+
+        let handle = std::thread::spawn(|| {
+            let x: Result<(), _> = Err(into_err(String::from(SOME_STRING)));
+            x
+        });
+        let y = handle.join().unwrap().unwrap_err();
+        assert_eq!(y.description(), SOME_STRING);
+    }
+
+    #[test]
+    fn test_err_macro() {
+        let x = err!("Foo").downcast::<StringError>().unwrap();
+        assert!(matches!(x.into_message(), Cow::Borrowed("Foo")));
+
+        let n = 42;
+        let y = err!("Foo {}", n).downcast::<StringError>().unwrap();
+        assert!(matches!(y.into_message(), Cow::Owned(ref m) if m == "Foo 42"));
+    }
+
+    fn bail_if_negative(x: i32) -> Result<i32, Box<dyn Error>> {
+        if x < 0 {
+            bail!("negative: {}", x);
+        }
+        Ok(x)
+    }
+
+    #[test]
+    fn test_bail_macro() {
+        assert_eq!(bail_if_negative(1).unwrap(), 1);
+        assert_eq!(bail_if_negative(-1).unwrap_err().description(),
+                   "negative: -1");
+    }
+
+    fn ensure_positive(x: i32) -> Result<i32, Box<dyn Error + Send + Sync>> {
+        ensure!(x != 0);
+        ensure!(x > 0, "not positive");
+        Ok(x)
+    }
+
+    #[test]
+    fn test_ensure_macro() {
+        assert_eq!(ensure_positive(1).unwrap(), 1);
+        let x = ensure_positive(0).unwrap_err();
+        assert_eq!(x.description(), "Condition failed: `x != 0`");
+        let y = ensure_positive(-1).unwrap_err();
+        assert!(y.is::<StringError>());
+        assert_eq!(y.description(), "not positive");
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+#[allow(deprecated)]
+mod no_std_tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+
+    static SOME_STRING: &str = "This is a String?!";
+
+    static CODE: ErrorCode = ErrorCode::new("E0001", "coded", "Explained.");
+
+    #[test]
+    fn test_new() {
+        let x = StringError::new(SOME_STRING);
+        assert_eq!(x.message(), SOME_STRING);
+        assert_eq!(x.description(), SOME_STRING);
+        assert_eq!(x.to_string(), SOME_STRING);
+        assert_eq!(x, SOME_STRING);
+        assert_eq!(x.kind(), ErrorKind::Other);
+        assert!(x.source().is_none());
+        assert_eq!(location(&x).unwrap().line(), line!() - 7);
+        assert_eq!(format!("{:?}", x),
+                   format!("{} (at {})", SOME_STRING, x.location()));
+    }
+
+    #[test]
+    fn test_static_message() {
+        let message: Option<&'static str> =
+            StringError::new(SOME_STRING).static_message();
+        assert!(core::ptr::eq(message.unwrap(), SOME_STRING));
+        assert!(core::ptr::eq(STATIC_ERROR.static_message().unwrap(),
+                              SOME_STRING));
+    }
+
+    #[test]
+    fn test_builders() {
+        let x = StringError::new("Foo")
+            .with_prefix(Prefix::Error)
+            .with_kind(ErrorKind::TimedOut)
+            .with_code(&CODE);
+        assert_eq!(x.to_string(), "Error: [E0001] Foo");
+        assert_eq!(format!("{:#}", x),
+                   format!("Error: [E0001] Foo (at {})", x.location()));
+        assert_eq!(kind(&x), Some(ErrorKind::TimedOut));
+        assert_eq!(code(&x), Some(&CODE));
+        assert_eq!(CODE.error().to_string(), "[E0001] coded");
+    }
+
+    static STATIC_ERROR: StringError = StringError::from_static(SOME_STRING);
+
+    #[test]
+    fn test_from_static() {
+        let x: &'static (dyn Error + Send + Sync) = &STATIC_ERROR;
+        assert_eq!(x.to_string(), SOME_STRING);
+        assert_eq!(location(x).unwrap().line(), line!() - 6);
+        assert_eq!(Report::new(x).to_string(),
+                   format!("Error: {}", SOME_STRING));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_boxed_errors() {
+        let x = new_err_with_source("outer", static_err("inner"));
+        assert_eq!(x.to_string(), "outer");
+        assert_eq!(x.source().unwrap().to_string(), "inner");
+        assert_eq!(into_err(String::from("Foo")).to_string(), "Foo");
+
+        let n = 42;
+        let y = err!("Foo {}", n).downcast::<StringError>().unwrap();
+        assert_eq!(*y, "Foo 42");
+        assert_eq!(None::<i32>.context("missing").unwrap_err().to_string(),
+                   "missing");
+    }
 }
@@ -0,0 +1,122 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Macros to create errors from format strings.
+
+/// Creates an error trait object from a format string.
+///
+/// If the message has no format arguments, this behaves like `static_err`
+/// and does not copy the message. Otherwise the message is formatted into
+/// an owned string, like `into_err(format!(...))`. Either way, the error
+/// is a `StringError`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate string_error;
+///
+/// # fn main() {
+/// let x = err!("Foo");
+/// assert_eq!(x.to_string(), "Foo");
+///
+/// let n = 42;
+/// let y = err!("Foo {}", n);
+/// assert_eq!(y.to_string(), "Foo 42");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! err {
+    ($($arg:tt)+) => {
+        $crate::__format_err(format_args!($($arg)+))
+    };
+}
+
+/// Returns early with an error created from a format string.
+///
+/// `bail!(...)` is equivalent to `return Err(err!(...))`, except that the
+/// `StringError` is converted with `From`. This way, `bail!` can be used in
+/// functions returning `Box<dyn Error>`, `Box<dyn Error + Send + Sync>` or
+/// `Report`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate string_error;
+///
+/// use std::error::Error;
+///
+/// fn check(x: i32) -> Result<(), Box<dyn Error>> {
+///     if x < 0 {
+///         bail!("{} is negative", x);
+///     }
+///     Ok(())
+/// }
+///
+/// # fn main() {
+/// assert!(check(1).is_ok());
+/// assert_eq!(check(-1).unwrap_err().to_string(), "-1 is negative");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! bail {
+    ($($arg:tt)+) => {
+        return ::core::result::Result::Err(::core::convert::From::from(
+            $crate::__format_error(format_args!($($arg)+))))
+    };
+}
+
+/// Returns early with an error if a condition is not satisfied.
+///
+/// `ensure!(cond, ...)` is equivalent to `if !cond { bail!(...); }`. If
+/// no message is given, the message names the failed condition.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate string_error;
+///
+/// use std::error::Error;
+///
+/// fn check(port: u32) -> Result<(), Box<dyn Error>> {
+///     ensure!(port > 0);
+///     ensure!(port < 65536, "port {} is out of range", port);
+///     Ok(())
+/// }
+///
+/// # fn main() {
+/// assert!(check(80).is_ok());
+/// assert_eq!(check(0).unwrap_err().to_string(),
+///            "Condition failed: `port > 0`");
+/// assert_eq!(check(70000).unwrap_err().to_string(),
+///            "port 70000 is out of range");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ensure {
+    ($cond:expr $(,)*) => {
+        if !$cond {
+            return ::core::result::Result::Err(::core::convert::From::from(
+                $crate::StringError::new(
+                    concat!("Condition failed: `", stringify!($cond), "`"))));
+        }
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::bail!($($arg)+);
+        }
+    };
+}
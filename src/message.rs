@@ -0,0 +1,228 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage of error messages that keeps short messages inline.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::fmt::{self, Write};
+use core::str;
+
+/// The number of bytes of a message that is stored inline.
+///
+/// Chosen so that an inline message takes as much space as the `String` of
+/// a heap message and the tag of the enum.
+const INLINE_CAPACITY: usize = 30;
+
+/// The message of a string error.
+///
+/// String constants are borrowed, messages of up to `INLINE_CAPACITY`
+/// bytes are stored inline and only longer messages are stored on the
+/// heap. Owned strings are kept as they are, without shrinking them, which
+/// would reallocate. Reference-counted strings are shared.
+pub(crate) enum Message {
+    Static(&'static str),
+    Inline(Inline),
+    Heap(String),
+    Shared(Arc<str>),
+}
+
+impl Message {
+    /// Copies a message, storing it inline if it is short enough.
+    pub(crate) fn copy(s: &str) -> Message {
+        match Inline::new(s) {
+            Some(inline) => Message::Inline(inline),
+            None => Message::Heap(String::from(s)),
+        }
+    }
+
+    /// Formats a message, storing it inline if it is short enough.
+    pub(crate) fn format(args: fmt::Arguments) -> Message {
+        if let Some(s) = args.as_str() {
+            return Message::Static(s);
+        }
+        let mut writer = Writer::Inline(Inline::default());
+        // Writing to an `Inline` or a `String` does not fail, only a
+        // `Display` implementation in `args` can.
+        writer.write_fmt(args)
+            .expect("a Display implementation returned an error unexpectedly");
+        match writer {
+            Writer::Inline(inline) => Message::Inline(inline),
+            Writer::Heap(s) => Message::Heap(s),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        match *self {
+            Message::Static(s) => s,
+            Message::Inline(ref inline) => inline.as_str(),
+            Message::Heap(ref s) => s,
+            Message::Shared(ref s) => s,
+        }
+    }
+
+    /// Returns the message, borrowed only for string constants.
+    pub(crate) fn into_cow(self) -> Cow<'static, str> {
+        match self {
+            Message::Static(s) => Cow::Borrowed(s),
+            Message::Inline(inline) => {
+                Cow::Owned(String::from(inline.as_str()))
+            }
+            Message::Heap(s) => Cow::Owned(s),
+            Message::Shared(s) => Cow::Owned(String::from(&*s)),
+        }
+    }
+}
+
+impl From<Cow<'static, str>> for Message {
+    /// Borrows string constants and takes ownership of longer owned
+    /// strings; short owned strings are moved inline.
+    fn from(s: Cow<'static, str>) -> Message {
+        match s {
+            Cow::Borrowed(s) => Message::Static(s),
+            Cow::Owned(s) => match Inline::new(&s) {
+                Some(inline) => Message::Inline(inline),
+                None => Message::Heap(s),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// A string of up to `INLINE_CAPACITY` bytes.
+#[derive(Default)]
+pub(crate) struct Inline {
+    len: u8,
+    bytes: [u8; INLINE_CAPACITY],
+}
+
+impl Inline {
+    fn new(s: &str) -> Option<Inline> {
+        let mut inline = Inline::default();
+        inline.push_str(s).then_some(inline)
+    }
+
+    /// Appends `s` if it fits.
+    fn push_str(&mut self, s: &str) -> bool {
+        let len = self.len as usize;
+        match self.bytes.get_mut(len..len + s.len()) {
+            Some(bytes) => {
+                bytes.copy_from_slice(s.as_bytes());
+                self.len += s.len() as u8;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole strings are appended, so the bytes are valid UTF-8.
+        str::from_utf8(&self.bytes[..self.len as usize])
+            .expect("inline message is valid UTF-8")
+    }
+}
+
+/// Writes inline until the message gets too long.
+enum Writer {
+    Inline(Inline),
+    Heap(String),
+}
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match *self {
+            Writer::Inline(ref mut inline) => {
+                if !inline.push_str(s) {
+                    let mut heap = String::from(inline.as_str());
+                    heap.push_str(s);
+                    *self = Writer::Heap(heap);
+                }
+            }
+            Writer::Heap(ref mut heap) => heap.push_str(s),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+    use core::mem::size_of;
+
+    const SHORT: &str = "unexpected token";
+    const LONG: &str = "unexpected token at the end of the input";
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_size() {
+        assert_eq!(size_of::<Message>(), size_of::<String>() + 8);
+    }
+
+    #[test]
+    fn test_copy() {
+        assert!(matches!(Message::copy(SHORT), Message::Inline(_)));
+        assert!(matches!(Message::copy(LONG), Message::Heap(_)));
+        assert!(matches!(Message::copy(""), Message::Inline(_)));
+        let exact = "a".repeat(INLINE_CAPACITY);
+        assert!(matches!(Message::copy(&exact), Message::Inline(_)));
+        assert_eq!(Message::copy(SHORT).as_str(), SHORT);
+        assert_eq!(Message::copy(LONG).as_str(), LONG);
+        assert_eq!(Message::copy(&exact).as_str(), exact);
+    }
+
+    #[test]
+    fn test_from_cow() {
+        let x = Message::from(Cow::Borrowed(LONG));
+        assert!(matches!(x, Message::Static(s) if std::ptr::eq(s, LONG)));
+        let y = Message::from(Cow::Owned(String::from(SHORT)));
+        assert!(matches!(y, Message::Inline(_)));
+        let z = Message::from(Cow::Owned(String::from(LONG)));
+        assert!(matches!(z, Message::Heap(_)));
+        assert_eq!(z.into_cow(), LONG);
+    }
+
+    #[test]
+    fn test_owned_string_is_moved() {
+        let mut s = String::with_capacity(2 * LONG.len());
+        s.push_str(LONG);
+        let (ptr, capacity) = (s.as_ptr(), s.capacity());
+        let x = Message::from(Cow::Owned(s));
+        assert!(matches!(x, Message::Heap(ref s)
+                         if s.as_ptr() == ptr && s.capacity() == capacity));
+        assert!(matches!(x.into_cow(),
+                         Cow::Owned(ref s) if s.as_ptr() == ptr));
+    }
+
+    #[test]
+    fn test_format() {
+        let n = 42;
+        let x = Message::format(format_args!("token {}", n));
+        assert!(matches!(x, Message::Inline(_)));
+        assert_eq!(x.as_str(), "token 42");
+        let y = Message::format(format_args!("{} {}", LONG, n));
+        assert!(matches!(y, Message::Heap(_)));
+        assert_eq!(y.as_str(), format!("{} 42", LONG));
+        let z = Message::format(format_args!("{}{}", "Ä".repeat(15), 'ß'));
+        assert_eq!(z.as_str(), format!("{}ß", "Ä".repeat(15)));
+        assert!(matches!(Message::format(format_args!("static")),
+                         Message::Static("static")));
+    }
+}
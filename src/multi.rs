@@ -0,0 +1,260 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An error that holds several errors.
+
+use alloc::boxed::Box;
+use alloc::vec::{self, Vec};
+use core::error::Error;
+use core::fmt::{self, Write};
+use core::slice;
+
+use crate::report::{Indented, Report};
+
+/// A collection of errors that is an error itself.
+///
+/// Useful when an operation reports all failures instead of stopping at the
+/// first one, e.g. when validating input or running a batch of jobs. The
+/// errors are displayed numbered, each with its chain of sources on one
+/// line.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let mut errors = MultiError::new();
+/// errors.push(static_err("Missing name"));
+/// errors.push(new_err_with_source("Invalid port",
+///                                 static_err("Not a number")));
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!(errors.to_string(), "\
+/// 2 errors occurred:
+///     1: Missing name
+///     2: Invalid port: Not a number");
+/// ```
+#[derive(Default)]
+pub struct MultiError {
+    errors: Vec<Box<dyn Error + Send + Sync>>,
+}
+
+impl MultiError {
+    /// Creates an empty collection of errors.
+    pub fn new() -> MultiError {
+        MultiError { errors: Vec::new() }
+    }
+
+    /// Adds an error.
+    pub fn push<E>(&mut self, error: E)
+        where E: Into<Box<dyn Error + Send + Sync>> {
+        self.errors.push(error.into());
+    }
+
+    /// Returns the number of errors.
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Returns whether there are no errors.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns an iterator over the errors.
+    pub fn iter(&self) -> slice::Iter<'_, Box<dyn Error + Send + Sync>> {
+        self.errors.iter()
+    }
+
+    /// Returns the errors.
+    pub fn into_vec(self) -> Vec<Box<dyn Error + Send + Sync>> {
+        self.errors
+    }
+
+    /// Returns `Ok(())` if there are no errors, and `Err(self)` otherwise.
+    pub fn into_result(self) -> Result<(), MultiError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Collects the values of an iterator of results, or all of its errors.
+    ///
+    /// Unlike collecting into a `Result`, this does not stop at the first
+    /// error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_error::MultiError;
+    ///
+    /// let numbers = ["1", "x", "3", "y"].iter().map(|s| s.parse::<i32>());
+    /// let errors = MultiError::from_results(numbers).unwrap_err();
+    /// assert_eq!(errors.len(), 2);
+    ///
+    /// let numbers = ["1", "2"].iter().map(|s| s.parse::<i32>());
+    /// assert_eq!(MultiError::from_results(numbers).unwrap(), [1, 2]);
+    /// ```
+    pub fn from_results<T, E, I>(results: I) -> Result<Vec<T>, MultiError>
+        where I: IntoIterator<Item = Result<T, E>>,
+              E: Into<Box<dyn Error + Send + Sync>> {
+        let mut values = Vec::new();
+        let mut errors = MultiError::new();
+        for result in results {
+            match result {
+                Ok(value) => values.push(value),
+                Err(e) => errors.push(e),
+            }
+        }
+        errors.into_result().map(|()| values)
+    }
+}
+
+impl Error for MultiError {
+    fn description(&self) -> &str {
+        "multiple errors occurred"
+    }
+}
+
+impl fmt::Display for MultiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.len() {
+            0 => return f.write_str("no errors occurred"),
+            1 => f.write_str("1 error occurred:")?,
+            n => write!(f, "{} errors occurred:", n)?,
+        }
+        for (i, e) in self.iter().enumerate() {
+            let report = Report::new(&**e as &(dyn Error + 'static))
+                .pretty(false);
+            f.write_char('\n')?;
+            write!(Indented::new(f, "    1: ".len()), "{:>5}: {}", i + 1,
+                   report)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MultiError {
+    /// Writes the errors like `Display`.
+    ///
+    /// In alternate mode (`{:#?}`), writes the list of errors.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.debug_list().entries(self.iter()).finish()
+        } else {
+            fmt::Display::fmt(self, f)
+        }
+    }
+}
+
+impl From<Vec<Box<dyn Error + Send + Sync>>> for MultiError {
+    fn from(errors: Vec<Box<dyn Error + Send + Sync>>) -> MultiError {
+        MultiError { errors }
+    }
+}
+
+impl<E> FromIterator<E> for MultiError
+    where E: Into<Box<dyn Error + Send + Sync>> {
+    fn from_iter<I: IntoIterator<Item = E>>(errors: I) -> MultiError {
+        MultiError { errors: errors.into_iter().map(Into::into).collect() }
+    }
+}
+
+impl<E> Extend<E> for MultiError
+    where E: Into<Box<dyn Error + Send + Sync>> {
+    fn extend<I: IntoIterator<Item = E>>(&mut self, errors: I) {
+        self.errors.extend(errors.into_iter().map(Into::into));
+    }
+}
+
+impl IntoIterator for MultiError {
+    type Item = Box<dyn Error + Send + Sync>;
+    type IntoIter = vec::IntoIter<Box<dyn Error + Send + Sync>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MultiError {
+    type Item = &'a Box<dyn Error + Send + Sync>;
+    type IntoIter = slice::Iter<'a, Box<dyn Error + Send + Sync>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+#[allow(deprecated)]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+    use crate::{new_err_with_source, static_err, StringError};
+
+    #[test]
+    fn test_display() {
+        assert_eq!(MultiError::new().to_string(), "no errors occurred");
+
+        let one: MultiError = vec![static_err("Foo")].into();
+        assert_eq!(one.to_string(), "1 error occurred:\n    1: Foo");
+
+        let mut errors: MultiError = (0..9)
+            .map(|_| static_err("Foo"))
+            .collect();
+        errors.push(StringError::new("multi\nline"));
+        errors.push(new_err_with_source("Bar", static_err("Baz")));
+        let display = errors.to_string();
+        assert!(display.starts_with("11 errors occurred:\n    1: Foo\n"));
+        assert!(display.ends_with("\n   10: multi\n       line\
+                                   \n   11: Bar: Baz"));
+        assert_eq!(format!("{:?}", errors), display);
+        assert!(format!("{:#?}", errors).starts_with("[\n"));
+        assert_eq!(errors.description(), "multiple errors occurred");
+        assert!(errors.source().is_none());
+    }
+
+    #[test]
+    fn test_collection() {
+        let mut errors = MultiError::new();
+        assert!(errors.is_empty());
+        errors.extend(["Foo", "Bar"]);
+        errors.push(static_err("Baz"));
+        assert_eq!(errors.len(), 3);
+        let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, ["Foo", "Bar", "Baz"]);
+        let messages: Vec<_> = (&errors).into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(errors.into_iter().count(), 3);
+
+        assert!(MultiError::new().into_result().is_ok());
+        let one: MultiError = ["Foo"].into_iter().collect();
+        assert_eq!(one.into_result().unwrap_err().into_vec().len(), 1);
+    }
+
+    #[test]
+    fn test_from_results() {
+        let results = vec![Ok(1), Err(static_err("Foo")), Ok(2),
+                           Err(static_err("Bar"))];
+        let errors = MultiError::from_results(results).unwrap_err();
+        assert_eq!(errors.to_string(),
+                   "2 errors occurred:\n    1: Foo\n    2: Bar");
+
+        let results: Vec<Result<_, StringError>> = vec![Ok(1), Ok(2)];
+        assert_eq!(MultiError::from_results(results).unwrap(), [1, 2]);
+    }
+}
@@ -0,0 +1,504 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rendering of an error together with its chain of sources.
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::error::Error;
+use core::fmt::{self, Write};
+
+/// Renders an error and its chain of sources.
+///
+/// By default, the report spans multiple lines:
+///
+/// ```text
+/// Error: Failed to load config
+///
+/// Caused by:
+///     0: Failed to read /etc/x
+///     1: No such file or directory
+/// ```
+///
+/// Messages spanning multiple lines are indented to line up. With
+/// `pretty(false)`, the report is a single line instead, with the messages
+/// separated by colons: `Failed to load config: Failed to read /etc/x: No
+/// such file or directory`.
+///
+/// With `show_backtrace(true)` and the `std` feature, the first captured
+/// backtrace of a `StringError` in the chain is appended.
+///
+/// With the `alloc` feature, `Report` (i.e. `Report<Box<dyn Error + Send +
+/// Sync>>`) can also own the error. It converts from any `Send + Sync`
+/// error, including the boxed errors of this crate, so it can be returned
+/// from `main`, where its `Debug` output shows the report. Like the error,
+/// it is `Send` and `Sync`, so it can also be returned from a thread. For
+/// errors that are not `Send` or `Sync`, see `from_error`.
+///
+///
+/// ```should_panic
+/// #[macro_use]
+/// extern crate string_error;
+///
+/// use string_error::{Context, Report};
+///
+/// fn main() -> Result<(), Report> {
+///     std::fs::read_to_string("/does/not/exist")
+///         .context("Failed to load config")?;
+///     bail!("not reached");
+/// }
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let err = new_err_with_source("Failed to load config",
+///                               static_err("Not found"));
+///
+/// let report = Report::new(&*err);
+/// assert_eq!(report.to_string(),
+///            "Error: Failed to load config\n\n\
+///             Caused by:\n    \
+///                 0: Not found");
+///
+/// let report = Report::new(&*err).pretty(false);
+/// assert_eq!(report.to_string(), "Failed to load config: Not found");
+/// ```
+#[cfg(feature = "alloc")]
+pub struct Report<E = Box<dyn Error + Send + Sync>> {
+    error: E,
+    pretty: bool,
+    show_backtrace: bool,
+}
+
+/// Renders an error and its chain of sources.
+///
+/// See the documentation with the `alloc` feature.
+#[cfg(not(feature = "alloc"))]
+pub struct Report<E> {
+    error: E,
+    pretty: bool,
+    show_backtrace: bool,
+}
+
+impl<'a> Report<&'a (dyn Error + 'static)> {
+    /// Creates a report for an error.
+    pub fn new(error: &'a (dyn Error + 'static))
+               -> Report<&'a (dyn Error + 'static)> {
+        Report { error, pretty: true, show_backtrace: false }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Report<Box<dyn Error>> {
+    /// Creates a report that owns an error that may not be `Send` or
+    /// `Sync`.
+    ///
+    /// This also accepts `Box<dyn Error>`. The report is a
+    /// `Report<Box<dyn Error>>`, which is not `Send` or `Sync` either. A
+    /// `From` conversion for such errors would conflict with the one for
+    /// `Box<dyn Error + Send + Sync>`, so use `map_err` with `?`:
+    ///
+    /// ```
+    /// use std::error::Error;
+    /// use string_error::Report;
+    ///
+    /// fn parse(s: &str) -> Result<u8, Box<dyn Error>> {
+    ///     Ok(s.parse()?)
+    /// }
+    ///
+    /// fn run() -> Result<u8, Report<Box<dyn Error>>> {
+    ///     parse("300").map_err(Report::from_error)
+    /// }
+    ///
+    /// let report = run().unwrap_err();
+    /// assert_eq!(report.error().to_string(),
+    ///            "number too large to fit in target type");
+    /// ```
+    ///
+    /// The report includes a captured backtrace, see `show_backtrace`.
+    pub fn from_error<E>(error: E) -> Report<Box<dyn Error>>
+        where E: Into<Box<dyn Error>> {
+        Report { error: error.into(), pretty: true, show_backtrace: true }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<E> From<E> for Report
+    where E: Into<Box<dyn Error + Send + Sync>> {
+    /// Creates a report that owns the error.
+    ///
+    /// The report includes a captured backtrace, see `show_backtrace`.
+    fn from(error: E) -> Report {
+        Report { error: error.into(), pretty: true, show_backtrace: true }
+    }
+}
+
+impl<E: sealed::AsError> Report<E> {
+    /// Sets whether the report spans multiple lines (the default) or a
+    /// single line.
+    pub fn pretty(mut self, pretty: bool) -> Report<E> {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Sets whether a captured backtrace is included in the report.
+    ///
+    /// This is off by default for `Report::new` and on for reports that own
+    /// the error. Only backtraces captured by a `StringError` are found,
+    /// and only with the `std` feature.
+    pub fn show_backtrace(mut self, show_backtrace: bool) -> Report<E> {
+        self.show_backtrace = show_backtrace;
+        self
+    }
+
+    /// Returns the error.
+    pub fn error(&self) -> &(dyn Error + 'static) {
+        self.error.as_error()
+    }
+
+    fn fmt_single_line(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error())?;
+        for e in Sources(self.error().source()) {
+            write!(f, ": {}", e)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the multi-line report, optionally without the leading
+    /// `"Error: "`.
+    fn fmt_multi_line(&self, f: &mut fmt::Formatter, header: bool)
+                      -> fmt::Result {
+        if header {
+            f.write_str("Error: ")?;
+        }
+        write!(Indented::new(f, "Error: ".len()), "{}", self.error())?;
+        fmt_causes(f, self.error())
+    }
+
+    #[cfg(feature = "std")]
+    fn fmt_backtrace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use std::backtrace::BacktraceStatus;
+
+        if !self.show_backtrace {
+            return Ok(());
+        }
+        let backtrace = Sources(Some(self.error()))
+            .filter_map(crate::backtrace)
+            .find(|b| b.status() == BacktraceStatus::Captured);
+        if let Some(backtrace) = backtrace {
+            write!(f, "\n\nStack backtrace:\n{}", backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: sealed::AsError> fmt::Display for Report<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.pretty {
+            self.fmt_multi_line(f, true)?;
+        } else {
+            self.fmt_single_line(f)?;
+        }
+        #[cfg(feature = "std")]
+        self.fmt_backtrace(f)?;
+        Ok(())
+    }
+}
+
+impl<E: sealed::AsError> fmt::Debug for Report<E> {
+    /// Writes the report like `Display`, but without the leading
+    /// `"Error: "` of the multi-line report.
+    ///
+    /// When `main` returns an error, Rust writes `"Error: "` followed by the
+    /// `Debug` output of the error, so the report shows up as with
+    /// `Display`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.pretty {
+            self.fmt_multi_line(f, false)?;
+        } else {
+            self.fmt_single_line(f)?;
+        }
+        #[cfg(feature = "std")]
+        self.fmt_backtrace(f)?;
+        Ok(())
+    }
+}
+
+mod sealed {
+    #[cfg(feature = "alloc")]
+    use alloc::boxed::Box;
+    use core::error::Error;
+
+    /// Types that a `Report` can be created for.
+    pub trait AsError {
+        fn as_error(&self) -> &(dyn Error + 'static);
+    }
+
+    impl AsError for &(dyn Error + 'static) {
+        fn as_error(&self) -> &(dyn Error + 'static) {
+            *self
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    impl AsError for Box<dyn Error> {
+        fn as_error(&self) -> &(dyn Error + 'static) {
+            &**self
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    impl AsError for Box<dyn Error + Send + Sync> {
+        fn as_error(&self) -> &(dyn Error + 'static) {
+            &**self
+        }
+    }
+}
+
+/// Writes the numbered list of the sources of an error, preceded by a blank
+/// line and `"Caused by:"`.
+///
+/// Writes nothing if the error has no source.
+pub(crate) fn fmt_causes(f: &mut fmt::Formatter,
+                         error: &(dyn Error + 'static)) -> fmt::Result {
+    let mut sources = Sources(error.source()).enumerate().peekable();
+    if sources.peek().is_some() {
+        f.write_str("\n\nCaused by:")?;
+    }
+    for (i, e) in sources {
+        f.write_char('\n')?;
+        write!(Indented::new(f, "    0: ".len()), "{:>5}: {}", i, e)?;
+    }
+    Ok(())
+}
+
+/// Iterates over an error and its sources.
+pub(crate) struct Sources<'a>(pub(crate) Option<&'a (dyn Error + 'static)>);
+
+impl<'a> Iterator for Sources<'a> {
+    type Item = &'a (dyn Error + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.0?;
+        self.0 = current.source();
+        Some(current)
+    }
+}
+
+/// Indents all but the first line written to it.
+///
+/// Empty lines are not indented.
+pub(crate) struct Indented<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    indent: usize,
+    line_start: bool,
+}
+
+impl<'a, 'b> Indented<'a, 'b> {
+    pub(crate) fn new(f: &'a mut fmt::Formatter<'b>, indent: usize)
+                      -> Indented<'a, 'b> {
+        Indented { f, indent, line_start: false }
+    }
+}
+
+impl Write for Indented<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.f.write_char('\n')?;
+                self.line_start = true;
+            }
+            if !line.is_empty() {
+                if self.line_start {
+                    write!(self.f, "{:1$}", "", self.indent)?;
+                    self.line_start = false;
+                }
+                self.f.write_str(line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{new_err_with_source, static_err, Context, StringError};
+    use std::io;
+
+    fn chain() -> Box<dyn Error + Send + Sync> {
+        let io = io::Error::new(io::ErrorKind::NotFound, "no such file");
+        new_err_with_source(
+            "Failed to load config",
+            Box::new(StringError::with_source("Failed to read /etc/x",
+                                              Box::new(io))))
+    }
+
+    #[test]
+    fn test_multi_line() {
+        let err = chain();
+        assert_eq!(Report::new(&*err).to_string(), "\
+Error: Failed to load config
+
+Caused by:
+    0: Failed to read /etc/x
+    1: no such file");
+    }
+
+    #[test]
+    fn test_single_line() {
+        let err = chain();
+        assert_eq!(Report::new(&*err).pretty(false).to_string(),
+                   "Failed to load config: Failed to read /etc/x: \
+                    no such file");
+    }
+
+    #[test]
+    fn test_no_sources() {
+        let err = static_err("Foo");
+        assert_eq!(Report::new(&*err).to_string(), "Error: Foo");
+        assert_eq!(Report::new(&*err).pretty(false).to_string(), "Foo");
+    }
+
+    #[test]
+    fn test_multi_line_messages_are_indented() {
+        let err = new_err_with_source("first\nsecond",
+                                      static_err("third\n\nfourth"));
+        assert_eq!(Report::new(&*err).to_string(), "\
+Error: first
+       second
+
+Caused by:
+    0: third
+
+       fourth");
+    }
+
+    #[test]
+    fn test_many_sources_are_aligned() {
+        let mut err = static_err("root\ncause");
+        for _ in 0..11 {
+            err = new_err_with_source("wrapped", err);
+        }
+        let report = Report::new(&*err).to_string();
+        assert!(report.ends_with("\n   10: root\n       cause"));
+    }
+
+    fn load_config() -> Result<(), Report> {
+        Err(chain())?;
+        Ok(())
+    }
+
+    fn check(x: i32) -> Result<i32, Report> {
+        ensure!(x >= 0);
+        if x == 0 {
+            bail!("zero: {}", x);
+        }
+        let n: u8 = "300".parse().context("parsing")?;
+        Ok(n.into())
+    }
+
+    #[test]
+    fn test_from() {
+        let report = load_config().unwrap_err();
+        assert_eq!(report.error().to_string(), "Failed to load config");
+        assert_eq!(format!("{:?}", report.show_backtrace(false)), "\
+Failed to load config
+
+Caused by:
+    0: Failed to read /etc/x
+    1: no such file");
+
+        let report = Report::from(io::Error::other("io"));
+        assert_eq!(report.pretty(false).to_string(), "io");
+        assert_eq!(Report::from("message").error().to_string(), "message");
+    }
+
+    #[derive(Debug)]
+    struct LocalError(std::rc::Rc<str>);
+
+    impl fmt::Display for LocalError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    impl Error for LocalError {}
+
+    fn local() -> Result<(), LocalError> {
+        Err(LocalError("not Send".into()))
+    }
+
+    fn boxed() -> Result<(), Box<dyn Error>> {
+        Err(Box::new(LocalError("boxed".into())))
+    }
+
+    fn load_local(is_local: bool) -> Result<(), Report<Box<dyn Error>>> {
+        if is_local {
+            local().map_err(Report::from_error)?;
+        }
+        boxed().map_err(Report::from_error)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_error() {
+        let report = load_local(true).unwrap_err().show_backtrace(false);
+        assert_eq!(report.to_string(), "Error: not Send");
+        let report = load_local(false).unwrap_err();
+        assert!(report.error().is::<LocalError>());
+        assert_eq!(format!("{:?}", report.show_backtrace(false)), "boxed");
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        let report = Report::from(static_err("Foo"));
+        assert_send_sync(&report);
+        let handle = std::thread::spawn(load_config);
+        let report = handle.join().unwrap().unwrap_err();
+        assert_eq!(report.error().to_string(), "Failed to load config");
+    }
+
+    #[test]
+    fn test_macros_and_context() {
+        assert_eq!(check(-1).unwrap_err().error().to_string(),
+                   "Condition failed: `x >= 0`");
+        assert_eq!(check(0).unwrap_err().error().to_string(), "zero: 0");
+        let report = check(1).unwrap_err().show_backtrace(false);
+        assert_eq!(format!("{}", report.pretty(false)),
+                   "parsing: number too large to fit in target type");
+    }
+
+    #[test]
+    fn test_debug_indents_under_error_prefix() {
+        let report = Report::from(static_err("first\nsecond"))
+            .show_backtrace(false);
+        assert_eq!(format!("Error: {:?}", report),
+                   "Error: first\n       second");
+    }
+
+    #[test]
+    fn test_show_backtrace() {
+        let err = chain();
+        let report = Report::new(&*err).show_backtrace(true).to_string();
+        let captured = crate::backtrace(&*err).unwrap().status()
+            == std::backtrace::BacktraceStatus::Captured;
+        assert_eq!(report.contains("\n\nStack backtrace:\n"), captured);
+        assert!(report.starts_with(&Report::new(&*err).to_string()));
+    }
+}
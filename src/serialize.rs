@@ -0,0 +1,540 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serialization of errors with serde.
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::report::Sources;
+use crate::{ErrorCode, ErrorKind, StringError, Value};
+
+/// A serializable snapshot of an error and its chain of sources.
+///
+/// `ErrorRecord::from_error` captures the message, kind, code and fields of
+/// a `StringError` (or the message, kind and code of any other error), and
+/// the messages of all its sources. A deserialized record is an error
+/// itself: it displays like the original error, and its `Error::source`
+/// chain yields the messages of the original sources.
+///
+/// `StringError` serializes the same way, so a serialized `StringError` can
+/// be deserialized as an `ErrorRecord`. A record passed to `from_error`,
+/// also as a boxed or shared error, is copied as it is, so forwarding a
+/// deserialized error keeps its kind, code and fields.
+///
+/// Field values are serialized as plain strings, numbers and booleans, so
+/// integers lose their signedness: a non-negative integer is deserialized as
+/// `Value::U64` and a negative one as `Value::I64`, e.g. a field created
+/// from `42i32` comes back as `Value::U64(42)`.
+///
+/// Kinds are serialized by name, e.g. `"NotFound"`. A custom kind whose
+/// name matches a built-in kind is deserialized as the built-in kind by
+/// `error_kind`.
+///
+/// # Examples
+///
+/// ```
+/// use std::error::Error;
+/// use string_error::*;
+///
+/// let err = StringError::with_source("Failed to load config",
+///                                    static_err("file not found"))
+///     .with_kind(ErrorKind::NotFound)
+///     .with_field("path", "/etc/x");
+///
+/// let json = serde_json::to_string(&err).unwrap();
+/// let record: ErrorRecord = serde_json::from_str(&json).unwrap();
+/// assert_eq!(record.to_string(), "Failed to load config");
+/// assert_eq!(record.error_kind(), Some(ErrorKind::NotFound));
+/// assert_eq!(record.source().unwrap().to_string(), "file not found");
+///
+/// let boxed: Box<dyn Error + Send + Sync> = Box::new(record);
+/// assert_eq!(boxed.to_string(), err.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorRecord {
+    message: String,
+    prefix: String,
+    kind: Option<String>,
+    code: Option<String>,
+    fields: Vec<(String, Value)>,
+    source: Option<Box<ErrorRecord>>,
+}
+
+impl ErrorRecord {
+    /// Captures an error and the messages of its sources.
+    pub fn from_error(e: &(dyn Error + 'static)) -> ErrorRecord {
+        let inner = crate::unshare(e);
+        if let Some(record) = inner.downcast_ref::<ErrorRecord>() {
+            return record.clone();
+        }
+        let mut record = ErrorRecord::from_message(e.to_string());
+        record.kind = crate::kind(e).map(|k| String::from(kind_name(k)));
+        if let Some(e) = inner.downcast_ref::<StringError>() {
+            record.message = String::from(e.message());
+            record.prefix = String::from(e.prefix().as_str());
+            record.fields = e.fields()
+                .map(|(key, value)| (String::from(key), value.clone()))
+                .collect();
+        }
+        if let Some(code) = crate::code(e) {
+            record.code = Some(String::from(code.code()));
+        }
+        if let Some(code) = inner.downcast_ref::<ErrorCode>() {
+            record.message = String::from(code.message());
+        }
+        let sources = Sources(e.source()).map(|e| e.to_string()).collect();
+        record.source = chain(sources);
+        record
+    }
+
+    fn from_message(message: String) -> ErrorRecord {
+        ErrorRecord {
+            message,
+            prefix: String::new(),
+            kind: None,
+            code: None,
+            fields: Vec::new(),
+            source: None,
+        }
+    }
+
+    /// Returns the error message, without prefix and code.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the name of the kind of the error, if it had one.
+    pub fn kind(&self) -> Option<&str> {
+        self.kind.as_deref()
+    }
+
+    /// Returns the kind of the error, if it had a built-in kind.
+    ///
+    /// Custom kinds cannot be restored, since `ErrorKind::Custom` holds a
+    /// `&'static str`; use `kind` to get their name.
+    pub fn error_kind(&self) -> Option<ErrorKind> {
+        self.kind.as_deref().and_then(kind_from_name)
+    }
+
+    /// Returns the error code, if any.
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+
+    /// Returns the value of the first field with the given key.
+    pub fn field(&self, key: &str) -> Option<&Value> {
+        self.fields.iter().find(|f| f.0 == key).map(|f| &f.1)
+    }
+
+    /// Returns an iterator over the key-value fields.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.fields.iter().map(|f| (&*f.0, &f.1))
+    }
+}
+
+impl Error for ErrorRecord {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|e| &**e as &(dyn Error + 'static))
+    }
+}
+
+impl fmt::Display for ErrorRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.prefix)?;
+        if let Some(ref code) = self.code {
+            write!(f, "[{}] ", code)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Turns the messages of a flattened source chain into nested records.
+fn chain(messages: Vec<String>) -> Option<Box<ErrorRecord>> {
+    messages.into_iter().rev().fold(None, |source, message| {
+        let mut record = ErrorRecord::from_message(message);
+        record.source = source;
+        Some(Box::new(record))
+    })
+}
+
+fn kind_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::NotFound => "NotFound",
+        ErrorKind::PermissionDenied => "PermissionDenied",
+        ErrorKind::ConnectionRefused => "ConnectionRefused",
+        ErrorKind::ConnectionReset => "ConnectionReset",
+        ErrorKind::ConnectionAborted => "ConnectionAborted",
+        ErrorKind::NotConnected => "NotConnected",
+        ErrorKind::AlreadyExists => "AlreadyExists",
+        ErrorKind::WouldBlock => "WouldBlock",
+        ErrorKind::InvalidInput => "InvalidInput",
+        ErrorKind::InvalidData => "InvalidData",
+        ErrorKind::TimedOut => "TimedOut",
+        ErrorKind::Interrupted => "Interrupted",
+        ErrorKind::Unsupported => "Unsupported",
+        ErrorKind::UnexpectedEof => "UnexpectedEof",
+        ErrorKind::OutOfMemory => "OutOfMemory",
+        ErrorKind::Other => "Other",
+        ErrorKind::Custom(name) => name,
+    }
+}
+
+fn kind_from_name(name: &str) -> Option<ErrorKind> {
+    Some(match name {
+        "NotFound" => ErrorKind::NotFound,
+        "PermissionDenied" => ErrorKind::PermissionDenied,
+        "ConnectionRefused" => ErrorKind::ConnectionRefused,
+        "ConnectionReset" => ErrorKind::ConnectionReset,
+        "ConnectionAborted" => ErrorKind::ConnectionAborted,
+        "NotConnected" => ErrorKind::NotConnected,
+        "AlreadyExists" => ErrorKind::AlreadyExists,
+        "WouldBlock" => ErrorKind::WouldBlock,
+        "InvalidInput" => ErrorKind::InvalidInput,
+        "InvalidData" => ErrorKind::InvalidData,
+        "TimedOut" => ErrorKind::TimedOut,
+        "Interrupted" => ErrorKind::Interrupted,
+        "Unsupported" => ErrorKind::Unsupported,
+        "UnexpectedEof" => ErrorKind::UnexpectedEof,
+        "OutOfMemory" => ErrorKind::OutOfMemory,
+        "Other" => ErrorKind::Other,
+        _ => return None,
+    })
+}
+
+/// The serialized form of an `ErrorRecord`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Wire<'a> {
+    message: Cow<'a, str>,
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    prefix: Cow<'a, str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Fields::is_empty")]
+    fields: Fields,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sources: Vec<Cow<'a, str>>,
+}
+
+impl Serialize for ErrorRecord {
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        let sources = Sources(self.source())
+            .map(|e| Cow::Owned(e.to_string()))
+            .collect();
+        Wire {
+            message: Cow::Borrowed(&self.message),
+            prefix: Cow::Borrowed(&self.prefix),
+            kind: self.kind.as_deref().map(Cow::Borrowed),
+            code: self.code.as_deref().map(Cow::Borrowed),
+            fields: Fields(self.fields.clone()),
+            sources,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+                                         -> Result<ErrorRecord, D::Error> {
+        let wire = Wire::deserialize(deserializer)?;
+        Ok(ErrorRecord {
+            message: wire.message.into_owned(),
+            prefix: wire.prefix.into_owned(),
+            kind: wire.kind.map(Cow::into_owned),
+            code: wire.code.map(Cow::into_owned),
+            fields: wire.fields.0,
+            source: chain(wire.sources.into_iter()
+                          .map(Cow::into_owned).collect()),
+        })
+    }
+}
+
+impl Serialize for StringError {
+    /// Serializes the error like `ErrorRecord::from_error`.
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        ErrorRecord::from_error(self).serialize(serializer)
+    }
+}
+
+/// Key-value fields, serialized as a map.
+#[derive(Default)]
+struct Fields(Vec<(String, Value)>);
+
+impl Fields {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for Fields {
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+                                         -> Result<Fields, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of fields")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A)
+                                            -> Result<Fields, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    fields.push(entry);
+                }
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Str(ref s) => serializer.serialize_str(s),
+            Value::I64(n) => serializer.serialize_i64(n),
+            Value::U64(n) => serializer.serialize_u64(n),
+            Value::F64(n) => serializer.serialize_f64(n),
+            Value::Bool(b) => serializer.serialize_bool(b),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    /// Deserializes a string, number or boolean.
+    ///
+    /// Non-negative integers are deserialized as `Value::U64` by most
+    /// formats.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+                                         -> Result<Value, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string, number or boolean")
+            }
+
+            fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+                Ok(Value::Bool(b))
+            }
+
+            fn visit_i64<E: de::Error>(self, n: i64) -> Result<Value, E> {
+                Ok(Value::I64(n))
+            }
+
+            fn visit_u64<E: de::Error>(self, n: u64) -> Result<Value, E> {
+                Ok(Value::U64(n))
+            }
+
+            fn visit_f64<E: de::Error>(self, n: f64) -> Result<Value, E> {
+                Ok(Value::F64(n))
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+                Ok(Value::from(String::from(s)))
+            }
+
+            fn visit_string<E: de::Error>(self, s: String)
+                                          -> Result<Value, E> {
+                Ok(Value::from(s))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{static_err, Prefix, SharedError};
+    use std::io;
+
+    static CODE: ErrorCode = ErrorCode::new("E0001", "coded", "Explained.");
+
+    fn round_trip<T: Serialize + ?Sized>(e: &T) -> ErrorRecord {
+        serde_json::from_str(&serde_json::to_string(e).unwrap()).unwrap()
+    }
+
+    fn chain_of(e: &(dyn Error + 'static)) -> Vec<String> {
+        Sources(Some(e)).map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_serialize_string_error() {
+        let err = StringError::with_source("outer", static_err("inner"))
+            .with_kind(ErrorKind::TimedOut)
+            .with_field("user_id", 42)
+            .with_field("path", "/etc/x");
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "message": "outer",
+            "kind": "TimedOut",
+            "fields": {"user_id": 42, "path": "/etc/x"},
+            "sources": ["inner"],
+        }));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let io = io::Error::new(io::ErrorKind::NotFound, "no such file");
+        let err = StringError::with_source(
+            "outer", Box::new(StringError::with_source("middle",
+                                                       Box::new(io))))
+            .with_prefix(Prefix::Error)
+            .with_kind(ErrorKind::Custom("throttled"))
+            .with_field("retry", true)
+            .with_field("delay", 1.5);
+        let record = round_trip(&err);
+        assert_eq!(record.to_string(), err.to_string());
+        assert_eq!(record.message(), "outer");
+        assert_eq!(record.kind(), Some("throttled"));
+        assert_eq!(record.error_kind(), None);
+        assert_eq!(record.field("retry"), Some(&Value::Bool(true)));
+        assert_eq!(record.field("delay"), Some(&Value::F64(1.5)));
+        assert_eq!(chain_of(&record), chain_of(&err));
+        assert_eq!(round_trip(&record), record);
+
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(record);
+        assert_eq!(boxed.to_string(), "Error: outer");
+    }
+
+    #[test]
+    fn test_forward_record() {
+        let err = StringError::with_source("outer", static_err("inner"))
+            .with_prefix(Prefix::Error)
+            .with_kind(ErrorKind::NotFound)
+            .with_code(&CODE)
+            .with_field("n", 1);
+        let record = round_trip(&err);
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(record.clone());
+        let forwarded = ErrorRecord::from_error(&*boxed);
+        assert_eq!(forwarded, record);
+        assert_eq!(serde_json::to_value(&forwarded).unwrap(),
+                   serde_json::to_value(&err).unwrap());
+        let shared = SharedError::from(boxed);
+        assert_eq!(ErrorRecord::from_error(&shared), record);
+    }
+
+    #[test]
+    fn test_integer_fields() {
+        let err = StringError::new("Foo")
+            .with_field("user_id", 42)
+            .with_field("offset", -1)
+            .with_field("count", 7u8);
+        let record = round_trip(&err);
+        assert_eq!(record.field("user_id"), Some(&Value::U64(42)));
+        assert_eq!(record.field("offset"), Some(&Value::I64(-1)));
+        assert_eq!(record.field("count"), Some(&Value::U64(7)));
+        assert_eq!(record.field("user_id").unwrap().to_string(), "42");
+    }
+
+    #[test]
+    fn test_round_trip_code() {
+        let record = round_trip(&CODE.error());
+        assert_eq!(record.code(), Some("E0001"));
+        assert_eq!(record.message(), "coded");
+        assert_eq!(record.to_string(), "[E0001] coded");
+
+        let record = ErrorRecord::from_error(&CODE);
+        assert_eq!(record.to_string(), CODE.to_string());
+    }
+
+    #[test]
+    fn test_round_trip_code_with_custom_message() {
+        let err = StringError::new("custom message").with_code(&CODE);
+        let record = round_trip(&err);
+        assert_eq!(record.code(), Some("E0001"));
+        assert_eq!(record.message(), "custom message");
+        assert_eq!(record.to_string(), "[E0001] custom message");
+        assert_eq!(record.to_string(), err.to_string());
+    }
+
+    #[test]
+    fn test_from_shared_error() {
+        let err = StringError::with_source("outer", static_err("inner"))
+            .with_prefix(Prefix::Error)
+            .with_kind(ErrorKind::NotFound)
+            .with_code(&CODE)
+            .with_field("user_id", 42);
+        let record = ErrorRecord::from_error(&SharedError::new(err));
+        assert_eq!(record.message(), "outer");
+        assert_eq!(record.error_kind(), Some(ErrorKind::NotFound));
+        assert_eq!(record.code(), Some("E0001"));
+        assert_eq!(record.field("user_id"), Some(&Value::I64(42)));
+        assert_eq!(record.to_string(), "Error: [E0001] outer");
+        assert_eq!(chain_of(&record), ["Error: [E0001] outer", "inner"]);
+    }
+
+    #[test]
+    fn test_from_other_error() {
+        let io = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        let record = round_trip(&ErrorRecord::from_error(&io));
+        assert_eq!(record.to_string(), "denied");
+        assert_eq!(record.error_kind(), Some(ErrorKind::PermissionDenied));
+        assert!(record.source().is_none());
+
+        let record = ErrorRecord::from_error(&fmt::Error);
+        assert_eq!(record.kind(), None);
+    }
+
+    #[test]
+    fn test_deserialize_minimal() {
+        let record: ErrorRecord =
+            serde_json::from_str(r#"{"message": "Foo"}"#).unwrap();
+        assert_eq!(record.to_string(), "Foo");
+        assert_eq!(record.kind(), None);
+        assert_eq!(record.fields().count(), 0);
+    }
+
+    #[test]
+    fn test_kind_names() {
+        for &kind in &[ErrorKind::NotFound, ErrorKind::OutOfMemory,
+                       ErrorKind::Other] {
+            assert_eq!(kind_from_name(kind_name(kind)), Some(kind));
+        }
+    }
+}
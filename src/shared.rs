@@ -0,0 +1,241 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Errors that can be cloned cheaply and shared between threads.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::error::Error;
+use core::fmt;
+
+use crate::StringError;
+
+/// An error that can be cloned by incrementing a reference count.
+///
+/// Errors, including `StringError`, are usually not `Clone`. Wrapping an
+/// error in a `SharedError` allows handing the same failure to many
+/// receivers, e.g. all waiters for a cached result, without formatting it
+/// again. `SharedError` implements `Error` by forwarding to the wrapped
+/// error, so its `Display` output and its sources are those of the wrapped
+/// error. `kind`, `code`, `location`, `backtrace` and serialization also
+/// look at the wrapped error.
+///
+/// `SharedError` is a type of its own, so a boxed `SharedError` downcasts
+/// to `SharedError`, not to the wrapped error:
+/// `Box<dyn Error>::downcast_ref::<StringError>()` returns `None` for it.
+/// Downcast to `SharedError` first and then use `downcast_ref`, or use the
+/// functions above, which look inside.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::*;
+///
+/// let x = SharedError::new(StringError::new("Connection lost"));
+/// let y = x.clone();
+/// assert!(SharedError::ptr_eq(&x, &y));
+/// assert_eq!(y.to_string(), "Connection lost");
+/// assert!(y.downcast_ref::<StringError>().is_some());
+///
+/// let boxed: Box<dyn std::error::Error> = Box::new(y);
+/// assert!(boxed.downcast_ref::<StringError>().is_none());
+/// let shared = boxed.downcast_ref::<SharedError>().unwrap();
+/// assert!(shared.downcast_ref::<StringError>().is_some());
+/// ```
+#[derive(Clone)]
+pub struct SharedError {
+    error: Arc<dyn Error + Send + Sync>,
+}
+
+impl SharedError {
+    /// Wraps an error.
+    pub fn new<E>(error: E) -> SharedError
+        where E: Error + Send + Sync + 'static {
+        SharedError { error: Arc::new(error) }
+    }
+
+    /// Returns the wrapped error.
+    pub fn get_ref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.error
+    }
+
+    /// Returns the wrapped error if it is of type `E`.
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+        where E: Error + 'static {
+        self.error.downcast_ref()
+    }
+
+    /// Returns whether both errors are clones of the same error.
+    pub fn ptr_eq(this: &SharedError, other: &SharedError) -> bool {
+        Arc::ptr_eq(&this.error, &other.error)
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for SharedError {
+    /// Wraps a boxed error, moving it into the shared allocation.
+    fn from(error: Box<dyn Error + Send + Sync>) -> SharedError {
+        SharedError { error: Arc::from(error) }
+    }
+}
+
+impl From<StringError> for SharedError {
+    fn from(error: StringError) -> SharedError {
+        SharedError::new(error)
+    }
+}
+
+impl Error for SharedError {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        (*self.error).description()
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&*self.error, f)
+    }
+}
+
+impl fmt::Debug for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.error, f)
+    }
+}
+
+/// Creates a shared error for a reference-counted string (`Arc<str>`).
+///
+/// The error is a `StringError` that keeps a reference to the string
+/// instead of copying it, see `StringError::from_shared`, wrapped in a
+/// `SharedError`. Cloning the error does not copy the message either.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use string_error::*;
+///
+/// let message: Arc<str> = Arc::from("Upstream timed out");
+/// let x = shared_err(message.clone());
+/// let e = x.downcast_ref::<StringError>().unwrap();
+/// assert!(std::ptr::eq(e.message(), &*message));
+///
+/// let y = shared_err(format!("Upstream timed out after {}s", 30));
+/// assert_eq!(y.to_string(), "Upstream timed out after 30s");
+/// ```
+#[track_caller]
+pub fn shared_err<M>(message: M) -> SharedError
+    where M: Into<Arc<str>> {
+    SharedError::new(StringError::from_shared(message.into()))
+}
+
+#[cfg(all(test, feature = "std"))]
+#[allow(deprecated)]
+mod tests {
+    use super::*;
+    use crate::{backtrace, code, kind, location, new_err_with_source,
+                static_err, ErrorCode, ErrorKind, Report};
+    use std::backtrace::BacktraceStatus;
+    use std::io;
+
+    fn assert_send_sync_clone<T: Send + Sync + Clone>(_: &T) {}
+
+    #[test]
+    fn test_fan_out() {
+        let x = shared_err(format!("Failed after {} retries", 3));
+        assert_send_sync_clone(&x);
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let x = x.clone();
+                std::thread::spawn(move || x)
+            })
+            .collect();
+        for waiter in waiters {
+            let y = waiter.join().unwrap();
+            assert!(SharedError::ptr_eq(&x, &y));
+            assert_eq!(y.to_string(), "Failed after 3 retries");
+        }
+    }
+
+    #[test]
+    fn test_forwarding() {
+        let x = SharedError::from(new_err_with_source("Foo",
+                                                      static_err("Bar")));
+        assert_eq!(x.to_string(), "Foo");
+        assert_eq!(x.description(), "Foo");
+        assert_eq!(x.source().unwrap().to_string(), "Bar");
+        assert_eq!(format!("{:?}", x), format!("{:?}", x.get_ref()));
+
+        let y = SharedError::from(StringError::new("Baz"));
+        assert_eq!(location(y.get_ref()).unwrap().line(), line!() - 1);
+        assert!(y.downcast_ref::<std::io::Error>().is_none());
+    }
+
+    #[test]
+    fn test_shared_message() {
+        let message: Arc<str> = Arc::from("Foo");
+        let x = shared_err(message.clone());
+        assert_eq!(Arc::strong_count(&message), 2);
+        let e = x.downcast_ref::<StringError>().unwrap();
+        assert_eq!(e.message(), "Foo");
+        assert_eq!(location(e).unwrap().line(), line!() - 4);
+        let y = x.clone();
+        assert_eq!(Arc::strong_count(&message), 2);
+        drop((x, y));
+        assert_eq!(Arc::strong_count(&message), 1);
+
+        let z = StringError::from_shared(message.clone());
+        assert!(std::ptr::eq(z.message(), &*message));
+        assert_eq!(z.into_message(), "Foo");
+        assert_eq!(Arc::strong_count(&message), 1);
+        assert_eq!(shared_err(format!("Foo {}", 42)).to_string(), "Foo 42");
+    }
+
+    #[test]
+    fn test_boxed_downcast() {
+        let x: Box<dyn Error> = Box::new(shared_err("Foo"));
+        assert!(x.downcast_ref::<StringError>().is_none());
+        let shared = x.downcast_ref::<SharedError>().unwrap();
+        assert_eq!(shared.downcast_ref::<StringError>().unwrap(), "Foo");
+    }
+
+    #[test]
+    fn test_inspection() {
+        static CODE: ErrorCode = ErrorCode::new("E0001", "Foo", "");
+
+        let x = SharedError::new(StringError::new("Foo")
+                                 .with_kind(ErrorKind::NotFound)
+                                 .with_code(&CODE));
+        assert_eq!(kind(&x), Some(ErrorKind::NotFound));
+        assert_eq!(code(&x), Some(&CODE));
+        assert_eq!(location(&x).unwrap().line(), line!() - 5);
+        let captured = backtrace(&x).unwrap().status()
+            == BacktraceStatus::Captured;
+        let report = Report::new(&x).show_backtrace(true).to_string();
+        assert_eq!(report.contains("\n\nStack backtrace:\n"), captured);
+
+        let y = SharedError::new(x.clone());
+        assert_eq!(kind(&y), Some(ErrorKind::NotFound));
+        assert_eq!(code(&y), Some(&CODE));
+
+        let io = io::Error::new(io::ErrorKind::TimedOut, "io");
+        assert_eq!(kind(&SharedError::new(io)), Some(ErrorKind::TimedOut));
+        assert_eq!(kind(&SharedError::from(static_err("Bar"))),
+                   Some(ErrorKind::Other));
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An owned error that is a single pointer wide.
+
+use alloc::boxed::Box;
+use core::error::Error;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+
+/// An owned error trait object behind a thin pointer.
+///
+/// A `Box<dyn Error>` is two words wide: a pointer to the error and a
+/// pointer to its vtable. `ThinError` stores the vtable in the same
+/// allocation as the error, in front of it, so it is one word wide, and so
+/// are `Option<ThinError>` and `Result<(), ThinError>`. Wrapping an error
+/// takes a single allocation, like boxing it.
+///
+/// Any error converts into a `ThinError` with `From`, so `?` works in
+/// functions returning it; an error that is already boxed is wrapped with
+/// `from_boxed`. Like `Report`, `ThinError` does not implement `Error`
+/// itself, which would conflict with that conversion; it dereferences to
+/// `dyn Error + Send + Sync` instead, and `into_boxed` returns the error as
+/// a trait object. `ThinError` converts into `Box<dyn Error + Send + Sync>`
+/// and `Box<dyn Error>` with `From`, so `?` also works in functions
+/// returning those, or anything they convert into, such as `Report`.
+///
+/// # Examples
+///
+/// ```
+/// use std::mem::size_of;
+/// use string_error::{StringError, ThinError};
+///
+/// fn parse(s: &str) -> Result<u8, ThinError> {
+///     Ok(s.parse()?)
+/// }
+///
+/// let x = parse("300").unwrap_err();
+/// assert_eq!(x.to_string(), "number too large to fit in target type");
+///
+/// let y = ThinError::from(StringError::new("Foo"));
+/// assert_eq!(y.downcast_ref::<StringError>().unwrap().message(), "Foo");
+///
+/// assert_eq!(size_of::<Result<(), ThinError>>(), size_of::<usize>());
+/// ```
+pub struct ThinError {
+    inner: NonNull<ErrorImpl<()>>,
+}
+
+// SAFETY: `ThinError` owns its `ErrorImpl`, and the constructors only
+// accept errors that are `Send` and `Sync`.
+unsafe impl Send for ThinError {}
+unsafe impl Sync for ThinError {}
+
+/// The allocation of a `ThinError`: the vtable followed by the error.
+///
+/// `repr(C)` keeps the vtable first for every `E`, so it can be read
+/// through a pointer to `ErrorImpl<()>`.
+///
+/// The functions of the vtable cast that pointer back to `ErrorImpl<E>`
+/// with the `E` the vtable was created for. They may only be called with
+/// the pointer of a `ThinError` whose vtable they belong to, which points
+/// to an allocation made by `Box<ErrorImpl<E>>`.
+#[repr(C)]
+struct ErrorImpl<E> {
+    vtable: &'static VTable,
+    error: E,
+}
+
+/// The operations on the error of an `ErrorImpl`, whose type is erased.
+struct VTable {
+    /// Returns the error as a trait object.
+    object_ref: unsafe fn(NonNull<ErrorImpl<()>>)
+        -> *const (dyn Error + Send + Sync),
+    /// Frees the allocation and returns the error boxed.
+    object_boxed: unsafe fn(NonNull<ErrorImpl<()>>)
+        -> Box<dyn Error + Send + Sync>,
+    /// Drops the error and frees the allocation.
+    object_drop: unsafe fn(NonNull<ErrorImpl<()>>),
+    /// Frees the allocation without dropping the error, which has been
+    /// moved out.
+    object_drop_front: unsafe fn(NonNull<ErrorImpl<()>>),
+}
+
+impl ThinError {
+    /// Creates a thin error.
+    pub fn new<E>(error: E) -> ThinError
+        where E: Error + Send + Sync + 'static {
+        let vtable = &VTable {
+            object_ref: object_ref::<E>,
+            object_boxed: object_boxed::<E>,
+            object_drop: object_drop::<E>,
+            object_drop_front: object_drop_front::<E>,
+        };
+        ThinError::construct(ErrorImpl { vtable, error })
+    }
+
+    /// Creates a thin error for an error that is already boxed.
+    ///
+    /// The box is moved into the allocation of the `ThinError` as it is,
+    /// so the error is not moved and `into_boxed` returns the same box.
+    pub fn from_boxed(error: Box<dyn Error + Send + Sync>) -> ThinError {
+        let vtable = &VTable {
+            object_ref: boxed_ref,
+            object_boxed: boxed_boxed,
+            object_drop: object_drop::<Box<dyn Error + Send + Sync>>,
+            object_drop_front: boxed_drop_front,
+        };
+        ThinError::construct(ErrorImpl { vtable, error })
+    }
+
+    fn construct<E>(inner: ErrorImpl<E>) -> ThinError {
+        let inner = NonNull::from(Box::leak(Box::new(inner)));
+        ThinError { inner: inner.cast() }
+    }
+
+    fn vtable(&self) -> &'static VTable {
+        // SAFETY: `inner` points to a live `ErrorImpl<E>`, and with
+        // `repr(C)`, its first field is the vtable for every `E`, at the
+        // same offset as in `ErrorImpl<()>`.
+        unsafe { (*self.inner.as_ptr()).vtable }
+    }
+
+    /// Returns the error as a trait object.
+    pub fn into_boxed(self) -> Box<dyn Error + Send + Sync> {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: the vtable belongs to `inner`. `object_boxed` frees the
+        // allocation, so `this` is not dropped.
+        unsafe { (this.vtable().object_boxed)(this.inner) }
+    }
+
+    /// Returns the error if it is of type `E`, and `self` otherwise.
+    ///
+    /// Neither case allocates.
+    pub fn downcast<E>(self) -> Result<E, ThinError>
+        where E: Error + 'static {
+        let error = match self.downcast_ref::<E>() {
+            Some(error) => error as *const E,
+            None => return Err(self),
+        };
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `error` points to a valid `E` owned by `this`, which is
+        // not dropped. After reading the error out, only the allocation is
+        // freed by `object_drop_front`, which does not drop the error again,
+        // and `error` is not used afterwards.
+        unsafe {
+            let error = ptr::read(error);
+            (this.vtable().object_drop_front)(this.inner);
+            Ok(error)
+        }
+    }
+}
+
+// The functions below implement the vtables. Each must only be called with
+// the pointer of a `ThinError` created with the same `E` (or by
+// `from_boxed` for the `boxed_` functions), see `ErrorImpl`. Except for
+// `object_ref`, they take ownership of the allocation, so the `ThinError`
+// must not be used or dropped afterwards.
+
+/// # Safety
+///
+/// `e` must point to a live `ErrorImpl<E>`. The returned pointer is valid
+/// as long as the `ThinError`.
+unsafe fn object_ref<E>(e: NonNull<ErrorImpl<()>>)
+    -> *const (dyn Error + Send + Sync)
+    where E: Error + Send + Sync + 'static {
+    let e = e.cast::<ErrorImpl<E>>().as_ptr();
+    // SAFETY: `e` points to a live `ErrorImpl<E>`; no reference to the
+    // whole `ErrorImpl` is created.
+    unsafe { ptr::addr_of!((*e).error) }
+}
+
+/// # Safety
+///
+/// `e` must point to an `ErrorImpl<E>` allocated by `Box`, which is freed.
+unsafe fn object_boxed<E>(e: NonNull<ErrorImpl<()>>)
+    -> Box<dyn Error + Send + Sync>
+    where E: Error + Send + Sync + 'static {
+    // SAFETY: the allocation was made by `Box<ErrorImpl<E>>` and is owned
+    // by the caller.
+    let e = unsafe { Box::from_raw(e.cast::<ErrorImpl<E>>().as_ptr()) };
+    Box::new(e.error)
+}
+
+/// # Safety
+///
+/// `e` must point to an `ErrorImpl<E>` allocated by `Box`, which is dropped
+/// and freed.
+unsafe fn object_drop<E>(e: NonNull<ErrorImpl<()>>) {
+    // SAFETY: as in `object_boxed`.
+    drop(unsafe { Box::from_raw(e.cast::<ErrorImpl<E>>().as_ptr()) });
+}
+
+/// # Safety
+///
+/// `e` must point to an `ErrorImpl<E>` allocated by `Box` whose error has
+/// been moved out. The allocation is freed without dropping the error.
+unsafe fn object_drop_front<E>(e: NonNull<ErrorImpl<()>>) {
+    // `ManuallyDrop<E>` is `repr(transparent)`, so `ErrorImpl<E>` and
+    // `ErrorImpl<ManuallyDrop<E>>` have the same layout, and freeing the
+    // latter uses the layout the allocation was made with.
+    let e = e.cast::<ErrorImpl<ManuallyDrop<E>>>().as_ptr();
+    // SAFETY: the allocation is owned by the caller, and the vtable, the
+    // only other field, needs no drop.
+    drop(unsafe { Box::from_raw(e) });
+}
+
+/// # Safety
+///
+/// Like `object_ref`, for a `ThinError` created by `from_boxed`.
+unsafe fn boxed_ref(e: NonNull<ErrorImpl<()>>)
+    -> *const (dyn Error + Send + Sync) {
+    let e = e.cast::<ErrorImpl<Box<dyn Error + Send + Sync>>>();
+    // SAFETY: `e` points to a live `ErrorImpl` holding the box.
+    unsafe { &*(*e.as_ptr()).error }
+}
+
+/// # Safety
+///
+/// Like `object_boxed`, for a `ThinError` created by `from_boxed`.
+unsafe fn boxed_boxed(e: NonNull<ErrorImpl<()>>)
+    -> Box<dyn Error + Send + Sync> {
+    let e = e.cast::<ErrorImpl<Box<dyn Error + Send + Sync>>>();
+    // SAFETY: the allocation was made by `Box` and is owned by the caller.
+    unsafe { Box::from_raw(e.as_ptr()).error }
+}
+
+/// # Safety
+///
+/// Like `object_drop_front`, for a `ThinError` created by `from_boxed`: the
+/// error in the inner box has been moved out. Both the outer allocation and
+/// the box of the error are freed, without dropping the error.
+unsafe fn boxed_drop_front(e: NonNull<ErrorImpl<()>>) {
+    // SAFETY: the caller passes ownership of the allocation.
+    let error = Box::into_raw(unsafe { boxed_boxed(e) });
+    // `ManuallyDrop` is `repr(transparent)`, so the cast keeps the vtable
+    // of the error, and with it the size and alignment the box was
+    // allocated with.
+    let error = error as *mut ManuallyDrop<dyn Error + Send + Sync>;
+    // SAFETY: `error` comes from `Box::into_raw`; dropping the
+    // `ManuallyDrop` only frees the memory.
+    drop(unsafe { Box::from_raw(error) });
+}
+
+impl<E> From<E> for ThinError
+    where E: Error + Send + Sync + 'static {
+    fn from(error: E) -> ThinError {
+        ThinError::new(error)
+    }
+}
+
+impl From<ThinError> for Box<dyn Error + Send + Sync + 'static> {
+    fn from(error: ThinError) -> Box<dyn Error + Send + Sync + 'static> {
+        error.into_boxed()
+    }
+}
+
+impl From<ThinError> for Box<dyn Error + 'static> {
+    fn from(error: ThinError) -> Box<dyn Error + 'static> {
+        error.into_boxed()
+    }
+}
+
+impl Drop for ThinError {
+    fn drop(&mut self) {
+        // SAFETY: the vtable belongs to `inner`, which is not used again.
+        unsafe { (self.vtable().object_drop)(self.inner) }
+    }
+}
+
+impl Deref for ThinError {
+    type Target = dyn Error + Send + Sync;
+
+    fn deref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        // SAFETY: the vtable belongs to `inner`, and the error lives as
+        // long as `self`.
+        unsafe { &*(self.vtable().object_ref)(self.inner) }
+    }
+}
+
+impl AsRef<dyn Error + Send + Sync> for ThinError {
+    fn as_ref(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &**self
+    }
+}
+
+impl fmt::Display for ThinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl fmt::Debug for ThinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{new_err_with_source, static_err, StringError};
+    use std::io;
+    use std::mem::size_of;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size_of::<ThinError>(), size_of::<usize>());
+        assert_eq!(size_of::<Option<ThinError>>(), size_of::<usize>());
+        assert_eq!(size_of::<Result<(), ThinError>>(), size_of::<usize>());
+        assert_eq!(size_of::<Result<(), Box<dyn Error>>>(),
+                   2 * size_of::<usize>());
+    }
+
+    fn read() -> Result<(), ThinError> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "no such file"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_from() {
+        let x = read().unwrap_err();
+        assert_eq!(x.to_string(), "no such file");
+        assert!(x.downcast_ref::<io::Error>().is_some());
+
+        let y = ThinError::from_boxed(new_err_with_source("Foo",
+                                                          static_err("Bar")));
+        assert_eq!(y.to_string(), "Foo");
+        assert_eq!(y.source().unwrap().to_string(), "Bar");
+        assert_eq!(format!("{:?}", y),
+                   format!("{:?}", y.downcast_ref::<StringError>().unwrap()));
+    }
+
+    fn read_boxed() -> Result<(), Box<dyn Error + Send + Sync>> {
+        read()?;
+        Ok(())
+    }
+
+    fn read_local() -> Result<(), Box<dyn Error>> {
+        read()?;
+        Ok(())
+    }
+
+    fn read_report() -> Result<(), crate::Report> {
+        read()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_into_box() {
+        let x = read_boxed().unwrap_err();
+        assert!(x.downcast_ref::<io::Error>().is_some());
+        let y = read_local().unwrap_err();
+        assert_eq!(y.to_string(), "no such file");
+        let z = read_report().unwrap_err();
+        assert!(z.error().is::<io::Error>());
+
+        let mut errors = crate::MultiError::new();
+        errors.push(read().unwrap_err());
+        assert_eq!(errors.iter().next().unwrap().to_string(), "no such file");
+    }
+
+    #[test]
+    fn test_downcast() {
+        let x = ThinError::new(StringError::new("Foo"));
+        let x = x.downcast::<io::Error>().unwrap_err();
+        let e = x.downcast::<StringError>().unwrap();
+        assert_eq!(e.message(), "Foo");
+
+        let y = ThinError::from_boxed(static_err("Bar"));
+        let e = y.downcast::<StringError>().unwrap();
+        assert_eq!(e.message(), "Bar");
+
+        let z = ThinError::new(StringError::new("Baz")).into_boxed();
+        assert_eq!(crate::location(&*z).unwrap().line(), line!() - 1);
+    }
+
+    #[test]
+    fn test_into_boxed() {
+        let boxed = static_err("Foo");
+        let ptr = &*boxed as *const (dyn Error + Send + Sync);
+        let x = ThinError::from_boxed(boxed).into_boxed();
+        assert!(ptr::addr_eq(&*x, ptr));
+    }
+
+    /// An error that holds a reference to its message.
+    #[derive(Debug)]
+    struct Counted(Arc<str>);
+
+    impl fmt::Display for Counted {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    impl Error for Counted {}
+
+    #[test]
+    fn test_drop() {
+        let message: Arc<str> = Arc::from("Foo");
+        let x = ThinError::new(Counted(message.clone()));
+        let y = ThinError::from_boxed(Box::new(Counted(message.clone())));
+        assert_eq!(Arc::strong_count(&message), 3);
+        drop((x, y));
+        assert_eq!(Arc::strong_count(&message), 1);
+    }
+}
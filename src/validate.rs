@@ -0,0 +1,209 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation that reports every invalid field.
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use core::error::Error;
+use core::fmt;
+
+use crate::{ErrorKind, MultiError, StringError, Value};
+
+/// Collects validation failures, each for the path of a field.
+///
+/// Every failed check adds a `StringError` of kind
+/// `ErrorKind::InvalidInput` with the message `"<path>: <message>"` and the
+/// path as the field `"path"`. `finish` returns all of them as a single
+/// `ValidationError`.
+///
+/// # Examples
+///
+/// ```
+/// use string_error::Validator;
+///
+/// let (port, host, workers) = (0, "", 4);
+///
+/// let mut v = Validator::new();
+/// v.nest("server", |v| {
+///     v.check(port > 0, "port", "must be positive");
+///     v.check(!host.is_empty(), "host", "must not be empty");
+/// });
+/// v.check(workers > 0, "workers", "must be positive");
+///
+/// let err = v.finish().unwrap_err();
+/// assert_eq!(err.paths().collect::<Vec<_>>(),
+///            ["server.port", "server.host"]);
+/// assert_eq!(err.to_string(), "\
+/// 2 errors occurred:
+///     1: server.port: must be positive
+///     2: server.host: must not be empty");
+/// ```
+#[derive(Debug, Default)]
+pub struct Validator {
+    prefix: String,
+    errors: MultiError,
+}
+
+impl Validator {
+    /// Creates a validator without failures.
+    pub fn new() -> Validator {
+        Validator { prefix: String::new(), errors: MultiError::new() }
+    }
+
+    /// Adds a failure for `path` unless `valid` is true.
+    ///
+    /// Returns `valid`, so that dependent checks can be skipped.
+    #[track_caller]
+    pub fn check<M>(&mut self, valid: bool, path: &str, message: M) -> bool
+        where M: Into<Cow<'static, str>> {
+        if !valid {
+            self.fail(path, message);
+        }
+        valid
+    }
+
+    /// Adds a failure for `path`.
+    #[track_caller]
+    pub fn fail<M>(&mut self, path: &str, message: M)
+        where M: Into<Cow<'static, str>> {
+        let path = self.path(path);
+        let error = StringError::new(format!("{}: {}", path, message.into()))
+            .with_kind(ErrorKind::InvalidInput)
+            .with_field("path", path);
+        self.errors.push(error);
+    }
+
+    /// Runs `f` with the paths of its checks prefixed by `segment` and a
+    /// dot.
+    pub fn nest<F>(&mut self, segment: &str, f: F)
+        where F: FnOnce(&mut Validator) {
+        let len = self.prefix.len();
+        self.prefix = self.path(segment);
+        self.prefix.push('.');
+        f(self);
+        self.prefix.truncate(len);
+    }
+
+    /// Returns whether no check has failed so far.
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns `Ok(())` if no check failed, and the failures otherwise.
+    pub fn finish(self) -> Result<(), ValidationError> {
+        self.errors.into_result().map_err(|errors| ValidationError { errors })
+    }
+
+    fn path(&self, path: &str) -> String {
+        format!("{}{}", self.prefix, path)
+    }
+}
+
+/// The failures collected by a `Validator`.
+///
+/// Displays like the `MultiError` of all failures.
+pub struct ValidationError {
+    errors: MultiError,
+}
+
+impl ValidationError {
+    /// Returns the paths of the invalid fields, in the order of the failed
+    /// checks.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.errors.iter().filter_map(|e| {
+            let e = e.downcast_ref::<StringError>()?;
+            match e.field("path") {
+                Some(Value::Str(path)) => Some(&**path),
+                _ => None,
+            }
+        })
+    }
+
+    /// Returns the failures.
+    pub fn errors(&self) -> &MultiError {
+        &self.errors
+    }
+
+    /// Returns the failures.
+    pub fn into_errors(self) -> MultiError {
+        self.errors
+    }
+}
+
+impl Error for ValidationError {
+    fn description(&self) -> &str {
+        "validation failed"
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.errors, f)
+    }
+}
+
+impl fmt::Debug for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.errors, f)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+
+    #[test]
+    fn test_valid() {
+        let mut v = Validator::new();
+        assert!(v.check(true, "port", "must be positive"));
+        assert!(v.is_valid());
+        assert!(v.finish().is_ok());
+    }
+
+    #[test]
+    fn test_paths() {
+        let mut v = Validator::new();
+        assert!(!v.check(false, "name", "is required"));
+        v.nest("server", |v| {
+            v.nest("tls", |v| v.fail("cert", "not found"));
+            v.check(false, "port", String::from("must be positive"));
+        });
+        v.check(false, "workers", "must be positive");
+        assert!(!v.is_valid());
+
+        let err = v.finish().unwrap_err();
+        assert_eq!(err.paths().collect::<Vec<_>>(),
+                   ["name", "server.tls.cert", "server.port", "workers"]);
+        assert_eq!(err.errors().len(), 4);
+        assert_eq!(format!("{:?}", err), err.to_string());
+
+        let messages: Vec<_> = err.into_errors().into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(messages[1], "server.tls.cert: not found");
+    }
+
+    #[test]
+    fn test_kind_and_location() {
+        let mut v = Validator::new();
+        v.check(false, "port", "must be positive");
+        let line = line!() - 1;
+        let errors = v.finish().unwrap_err().into_errors().into_vec();
+        assert_eq!(crate::kind(&*errors[0]), Some(ErrorKind::InvalidInput));
+        assert_eq!(crate::location(&*errors[0]).unwrap().line(), line);
+    }
+}
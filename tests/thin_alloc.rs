@@ -0,0 +1,77 @@
+// Copyright 2017 Ulrich Rhein
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Counts the allocations made by `ThinError`.
+//!
+//! The counting allocator is installed for the whole test binary, so this
+//! test is kept apart from the other tests.
+
+#![cfg(feature = "std")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io;
+
+use string_error::{static_err, StringError, ThinError};
+
+/// Counts the allocations of the current thread.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Returns the result of `f` and the number of allocations it made.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn test_allocations() {
+    let e = StringError::new("Foo");
+    let (x, n) = count_allocations(|| ThinError::new(e));
+    assert_eq!(n, 1);
+    let (x, n) = count_allocations(|| x.downcast::<io::Error>());
+    assert_eq!(n, 0);
+    let x = x.unwrap_err();
+    let (e, n) = count_allocations(|| x.downcast::<StringError>());
+    assert_eq!(n, 0);
+    assert_eq!(e.unwrap().message(), "Foo");
+
+    let boxed = static_err("Bar");
+    let (y, n) = count_allocations(|| ThinError::from_boxed(boxed));
+    assert_eq!(n, 1);
+    let (y, n) = count_allocations(|| y.downcast::<io::Error>());
+    assert_eq!(n, 0);
+    let y = y.unwrap_err();
+    let (e, n) = count_allocations(|| y.downcast::<StringError>());
+    assert_eq!(n, 0);
+    assert_eq!(e.unwrap().message(), "Bar");
+}